@@ -0,0 +1,114 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::time::Duration;
+
+use rodio::Source;
+
+const SAMPLE_RATE: u32 = 44100;
+const VOLUME: f32 = 0.25;
+
+/*Audio state shared between the emulator and the audio thread. The 16 byte pattern is stored as two 64 bit halves
+so it can be updated without locking*/
+pub struct AudioState {
+    playing: AtomicBool,
+    pattern_high: AtomicU64,
+    pattern_low: AtomicU64,
+    pitch: AtomicU8
+}
+
+impl AudioState {
+    pub fn new() -> AudioState {
+        AudioState {
+            playing: AtomicBool::new(false),
+            pattern_high: AtomicU64::new(0),
+            pattern_low: AtomicU64::new(0),
+            pitch: AtomicU8::new(64)
+        }
+    }
+
+    pub fn update(&self, playing: bool, pattern: [u8; 16], pitch: u8) {
+        let mut high = [0; 8];
+        let mut low = [0; 8];
+        high.copy_from_slice(&pattern[..8]);
+        low.copy_from_slice(&pattern[8..]);
+        self.pattern_high.store(u64::from_be_bytes(high), Ordering::Relaxed);
+        self.pattern_low.store(u64::from_be_bytes(low), Ordering::Relaxed);
+        self.pitch.store(pitch, Ordering::Relaxed);
+        self.playing.store(playing, Ordering::Relaxed);
+    }
+
+    //Returns bit 0-127 of the pattern, starting from the most significant bit of the first byte
+    fn bit(&self, bit: usize) -> bool {
+        if bit < 64 {
+            self.pattern_high.load(Ordering::Relaxed) & (1 << (63 - bit)) != 0
+        } else {
+            self.pattern_low.load(Ordering::Relaxed) & (1 << (127 - bit)) != 0
+        }
+    }
+
+    //XO-CHIP plays the pattern at 4000*2^((pitch-64)/48) bits per second
+    fn playback_rate(&self) -> f32 {
+        let pitch = self.pitch.load(Ordering::Relaxed) as f32;
+        4000.0 * 2f32.powf((pitch - 64.0) / 48.0)
+    }
+}
+
+//Endless source that plays the 1-bit XO-CHIP pattern buffer as a square wave while the sound timer is running
+pub struct PatternWave {
+    state: Arc<AudioState>,
+    position: f32
+}
+
+impl PatternWave {
+    pub fn new(state: Arc<AudioState>) -> PatternWave {
+        PatternWave {
+            state,
+            position: 0.0
+        }
+    }
+}
+
+impl Iterator for PatternWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.state.playing.load(Ordering::Relaxed) {
+            self.position = 0.0;
+            return Some(0.0);
+        }
+
+        let sample = if self.state.bit(self.position as usize) { VOLUME } else { -VOLUME };
+        self.position = (self.position + self.state.playback_rate() / SAMPLE_RATE as f32) % 128.0;
+        Some(sample)
+    }
+}
+
+impl Source for PatternWave {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
@@ -11,25 +11,136 @@ for more details. You should have received a copy of the GNU Affero General Publ
 not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::error::Error;
+use std::fmt;
+
 use rand::Rng;
 
+use crate::instruction::{decode_at, Instruction};
+use crate::quirks::Quirks;
+use crate::savestate::StateReader;
+
+//Resolutions of the low and high (SUPER-CHIP) resolution display modes
+const LORES_WIDTH: usize = 64;
+const LORES_HEIGHT: usize = 32;
+const HIRES_WIDTH: usize = 128;
+const HIRES_HEIGHT: usize = 64;
+
+//XO-CHIP extends the addressable memory to 64 KiB
+pub const MEMORY_SIZE: usize = 0x10000;
+pub const PROGRAM_START: usize = 0x200;
+
+//Locations of the small and big fonts in memory
+const FONT_ADDRESS: usize = 0x000;
+const BIG_FONT_ADDRESS: usize = 0x050;
+
+const FONTSET: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80  // F
+];
+
+//SUPER-CHIP 8x10 font, A-F are not in the original SUPER-CHIP but are used by modern programs
+const BIG_FONTSET: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0  // F
+];
+
+//Square wave played until a program loads its own audio pattern, 500 Hz at the default pitch
+const DEFAULT_PATTERN: [u8; 16] = [0xF0; 16];
+
 #[derive(Clone)]
 pub struct Chip8 {
     opcode: u16,
-    memory: [u8; 4096],
+    memory: Vec<u8>,
     v: [u8; 16],
     i: u16,
     pc: u16,
-    pub gfx: [u8; 2048],
+    pub gfx: Vec<u8>,
+    hires: bool,
+    planes: u8,
     delay_tmr: u8,
     sound_tmr: u8,
+    pattern: [u8; 16],
+    pitch: u8,
     stack: [u16; 16],
     sp: u16,
+    rpl: [u8; 16],
+    pub quirks: Quirks,
+    halted: Option<CpuError>,
     pub keypad: [u8; 16],
     pub debug_info: DebugInfo
 
 }
 
+//Result of successfully emulating one CPU cycle
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StepOutcome {
+    Continue,
+    Draw, //The display changed and needs to be drawn
+    Exit  //The program exited the interpreter with 00FD
+}
+
+//Memory read or written by an instruction, apart from fetching the instruction itself
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MemoryAccess {
+    Read { address: usize, len: usize },
+    Write { address: usize, len: usize }
+}
+
+//Errors that halt the CPU, each records the program counter and opcode of the instruction that caused it
+#[derive(Clone, PartialEq, Debug)]
+pub enum CpuError {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16, opcode: u16 },
+    StackUnderflow { pc: u16, opcode: u16 },
+    MemoryOutOfRange { pc: u16, opcode: u16, address: usize }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::UnknownOpcode { pc, opcode } =>
+                write!(f, "Unknown opcode {:#06X} at {:#06X}", opcode, pc),
+            CpuError::StackOverflow { pc, opcode } =>
+                write!(f, "Stack overflow by {:#06X} at {:#06X}", opcode, pc),
+            CpuError::StackUnderflow { pc, opcode } =>
+                write!(f, "Stack underflow by {:#06X} at {:#06X}", opcode, pc),
+            CpuError::MemoryOutOfRange { pc, opcode, address } =>
+                write!(f, "Memory access out of range at {:#07X} by {:#06X} at {:#06X}", address, opcode, pc)
+        }
+    }
+}
+
+impl Error for CpuError {}
+
 #[derive(Clone)]
 pub struct DebugInfo {
     pub opcode: u16,
@@ -45,18 +156,25 @@ pub struct DebugInfo {
 }
 
 impl Chip8 {
-    fn init(rom: [u8; 3584]) -> Chip8{
+    fn init(rom: Vec<u8>, quirks: Quirks) -> Chip8{
         let mut chip8 = Chip8{
             opcode: 0,
-            memory: [0; 4096],
+            memory: vec![0; MEMORY_SIZE],
             v: [0; 16],
             i: 0,
             pc: 0x200,
-            gfx: [0; 2048],
+            gfx: vec![0; LORES_WIDTH*LORES_HEIGHT],
+            hires: false,
+            planes: 1,
             delay_tmr: 0,
             sound_tmr: 0,
+            pattern: DEFAULT_PATTERN,
+            pitch: 64,
             stack: [0; 16],
             sp: 0,
+            rpl: [0; 16],
+            quirks,
+            halted: None,
             keypad: [0; 16],
             debug_info: DebugInfo {
                 opcode: 0,
@@ -71,549 +189,618 @@ impl Chip8 {
                 keypad: [0; 16]
             }
         };
-        let fontset: [u8; 80] = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80  // F
-        ];
-        for byte in 0..fontset.len() {
-            chip8.memory[byte] = fontset[byte];
+        chip8.memory[FONT_ADDRESS..FONT_ADDRESS+FONTSET.len()].copy_from_slice(&FONTSET);
+        chip8.memory[BIG_FONT_ADDRESS..BIG_FONT_ADDRESS+BIG_FONTSET.len()].copy_from_slice(&BIG_FONTSET);
+        chip8.memory[PROGRAM_START..PROGRAM_START+rom.len()].copy_from_slice(&rom);
+        chip8
+    }
+
+    //Width of the display in the current resolution mode
+    pub fn width(&self) -> usize {
+        if self.hires {
+            HIRES_WIDTH
+        } else {
+            LORES_WIDTH
         }
-        for byte in 0..rom.len() {
-            chip8.memory[0x200+byte] = rom[byte];
+    }
+
+    //Height of the display in the current resolution mode
+    pub fn height(&self) -> usize {
+        if self.hires {
+            HIRES_HEIGHT
+        } else {
+            LORES_HEIGHT
         }
-        return chip8;
     }
 
-    pub fn cycle(&mut self, debug: bool, draw: &mut bool, beep: &mut bool) {
-        //Fetch the Opcode from memory
-        let pc = self.pc as usize;
-        let op1 = self.memory[pc] as u16;
-        let op2 = self.memory[pc+1] as u16;
-        let opcode = (op1 << 8) | op2;
-        self.opcode = opcode;
+    //Switches between low and high resolution, the display is cleared when the resolution changes
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.gfx = vec![0; self.width()*self.height()];
+    }
 
-        if debug {
-            self.debug_info.opcode = self.opcode;
-            self.debug_info.i = self.i;
-            self.debug_info.pc = self.pc;
-            self.debug_info.delay_tmr = self.delay_tmr;
-            self.debug_info.sound_tmr = self.sound_tmr;
-            self.debug_info.sp = self.sp;
-            for i in 0..16 {
-                self.debug_info.v[i] = self.v[i];
-                self.debug_info.stack[i] = self.stack[i];
-                self.debug_info.keypad[i] = self.keypad[i];
-            }    
+    /*XORs a sprite onto the selected planes of the display with its top left corner at (x,y) and returns true if
+    a pixel was erased. Sprites are 8 pixels wide with one byte per row, or 16 pixels wide with two bytes per row.
+    When both planes are selected the sprite data for the second plane follows the data for the first plane.
+    The starting position always wraps around the display, the rest of the sprite is clipped with the clipping quirk*/
+    fn draw_sprite(&mut self, x: usize, y: usize, rows: usize, wide: bool) -> bool {
+        let width = self.width();
+        let height = self.height();
+        let x = x % width;
+        let y = y % height;
+        let bytes_per_row = if wide { 2 } else { 1 };
+        let mut address = self.i as usize;
+        let mut collision = false;
+
+        for plane in [1, 2] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+            for row in 0..rows {
+                for byte in 0..bytes_per_row {
+                    let line = self.memory[address + row*bytes_per_row + byte];
+                    for bit in 0..8 {
+                        let (pixel_x, pixel_y) = (x + byte*8 + bit, y + row);
+                        if self.quirks.clipping && (pixel_x >= width || pixel_y >= height) {
+                            continue;
+                        }
+                        if (line & (0x80 >> bit)) != 0 {
+                            let pos = ((pixel_y % height)*width) + (pixel_x % width);
+                            if self.gfx[pos] & plane != 0 {
+                                collision = true;
+                            }
+                            self.gfx[pos] ^= plane;
+                        }
+                    }
+                }
+            }
+            address += rows*bytes_per_row;
         }
 
-        //TODO: Instruction translations display hex
-        //Decode the instruction
-        let first = self.opcode & 0xF000;
-        let fl = self.opcode & 0xF00F;
-        let ftl = self.opcode & 0xF0FF;
-        
-        if self.opcode == 0x00E0 { //Clear Screen
-            for byte in 0..self.gfx.len() {
-                self.gfx[byte] = 0;
-            }
-            self.pc += 2;
+        collision
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "DISPLAY_CLEAR".to_string();
+    /*Scrolls the selected planes of the display by (dx,dy) pixels, positive values scroll right and down.
+    The pixels scrolled in from the edge of the display are blank*/
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        let width = self.width() as isize;
+        let height = self.height() as isize;
+        let old = self.gfx.clone();
+
+        for y in 0..height {
+            for x in 0..width {
+                let (src_x, src_y) = (x - dx, y - dy);
+                let scrolled = if src_x >= 0 && src_x < width && src_y >= 0 && src_y < height {
+                    old[(src_y*width + src_x) as usize] & self.planes
+                } else {
+                    0
+                };
+                let pos = (y*width + x) as usize;
+                self.gfx[pos] = (old[pos] & !self.planes) | scrolled;
             }
+        }
+    }
 
-        } else if self.opcode == 0x00EE { //Return from subroutine
-            self.sp -= 1;
-            self.pc = self.stack[self.sp as usize];
-            self.pc += 2;
+    //Length of the instruction at an address, which is 4 bytes for the XO-CHIP long load of I
+    fn instruction_len_at(&self, address: u16) -> u16 {
+        decode_at(&self.memory[address as usize..]).map_or(2, |(_, len)| len as u16)
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "RETURN".to_string();
-            }
+    //Halts the CPU with an error, every following cycle returns the same error
+    fn halt(&mut self, error: CpuError) -> CpuError {
+        self.halted = Some(error.clone());
+        error
+    }
 
-        } else if first == 0x1000 { //Jump to address NNN
-            self.pc = self.opcode & 0x0FFF;
+    //Checks that the len bytes starting at address are in memory
+    fn check_range(&self, address: usize, len: usize) -> Result<(), CpuError> {
+        if address + len > MEMORY_SIZE {
+            Err(CpuError::MemoryOutOfRange { pc: self.pc, opcode: self.opcode, address: address + len - 1 })
+        } else {
+            Ok(())
+        }
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "JUMP ".to_string();
-                self.debug_info.opcode_trans.push_str(&self.pc.to_string());
-            }
+    //The error that halted the CPU, if any
+    pub fn halted(&self) -> Option<&CpuError> {
+        self.halted.as_ref()
+    }
 
-        } else if first == 0x2000 { //Call subroutine at NNN
-            self.stack[self.sp as usize] = self.pc;
-            self.sp += 1;
-            self.pc = self.opcode & 0x0FFF;
+    //Fetches, decodes and executes the instruction at the program counter
+    pub fn cycle(&mut self, debug: bool) -> Result<StepOutcome, CpuError> {
+        if let Some(error) = &self.halted {
+            return Err(error.clone());
+        }
 
-            if debug {
-                self.debug_info.opcode_trans = "SUBROUTINE ".to_string();
-                self.debug_info.opcode_trans.push_str(&self.pc.to_string());
+        //Fetch the Opcode from memory, an instruction at the end of memory can be missing its last bytes
+        let pc = self.pc as usize;
+        self.opcode = if pc + 1 < MEMORY_SIZE { self.opcode_at(pc) } else { (self.memory[pc] as u16) << 8 };
+        let instruction = match decode_at(&self.memory[pc..]) {
+            Some((instruction, _)) => instruction,
+            None => {
+                let error = CpuError::MemoryOutOfRange { pc: self.pc, opcode: self.opcode, address: MEMORY_SIZE };
+                return Err(self.halt(error));
             }
+        };
 
-        } else if first == 0x3000 { //Skip next instruction if Vx == NN
-            self.pc += 2;
-            let reg = ((self.opcode & 0x0F00) >> 8) as usize;
-            let num = (self.opcode & 0x00FF) as u8;
-            if self.v[reg] == num {
-                self.pc += 2;
-            }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_EQUAL V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&num.to_string());
-            }
-
-        } else if first == 0x4000 { //Skip next instruction if Vx != NN
-            self.pc += 2;
-            let reg = ((self.opcode & 0x0F00) >> 8) as usize;
-            let num = (self.opcode & 0x00FF) as u8;
-            if self.v[reg] != num {
-                self.pc += 2;
-            }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_NOT_EQUAL V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&num.to_string());
-            }
-        
-        } else if fl == 0x5000 { //Skip next instruction if Vx == Vy
-            self.pc += 2;
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            if self.v[reg_x] == self.v[reg_y] {
-                self.pc += 2;
-            }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_EQUAL V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-        
-        } else if first == 0x6000 { //Set Vx == NN
-            let reg = ((self.opcode & 0x0F00) >> 8) as usize;
-            let num = (self.opcode & 0x00FF) as u8;
-            self.v[reg] = num;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SET V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&num.to_string());
-            }
-
-        } else if first == 0x7000 { //Add NN to Vx
-            let reg = ((self.opcode & 0x0F00) >> 8) as usize;
-            let num = (self.opcode & 0x00FF) as u8;
-            let result = self.v[reg].overflowing_add(num);
-            self.v[reg] = result.0;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "ADD V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&num.to_string());
-            }
-
-
-        } else if fl == 0x8000 { //Set Vx = Vy
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            self.v[reg_x] = self.v[reg_y];
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SET V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-
-        } else if fl == 0x8001 { //Set Vx = Vx | Vy
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            self.v[reg_x] = self.v[reg_x] | self.v[reg_y];
-            self.pc += 2;
-
-            
-            if debug {
-                self.debug_info.opcode_trans = "OR V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-    
-        } else if fl == 0x8002 { //Set Vx = Vx & Vy
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            self.v[reg_x] = self.v[reg_x] & self.v[reg_y];
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "AND V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-    
-        } else if fl == 0x8003 { //Set Vx = Vx ^ Vy
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            self.v[reg_x] = self.v[reg_x] ^ self.v[reg_y];
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "XOR V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-    
-        } else if fl == 0x8004 { //Add Vy to Vx
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            
-            let result = self.v[reg_x].overflowing_add(self.v[reg_y]);
-            self.v[reg_x] = result.0;
-
-            if result.1 {
-                self.v[0xF] = 1;
-            } else {
-                self.v[0xF] = 0;
-            }
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "ADD V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-    
-        } else if fl == 0x8005 { //Subtract Vy from Vx
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-
-            let result = self.v[reg_x].overflowing_sub(self.v[reg_y]);
-            self.v[reg_x] = result.0;
-
-            if result.1 {
-                self.v[0xF] = 0;
-            } else {
-                self.v[0xF] = 1;
-            }
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SUB V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-    
-        } else if fl == 0x8006 { //Bitshift Vx right by 1
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.v[0xF] = self.v[reg_x] & 0x1;
-            self.v[reg_x] = self.v[reg_x] >> 1;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "BITSHIFT_RIGHT V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
-    
-        } else if fl == 0x8007 { //subtract Vx from Vy
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-
-            let result = self.v[reg_y].overflowing_sub(self.v[reg_x]);
-            self.v[reg_x] = result.0;
-
-            if result.1 {
-                self.v[0xF] = 0;
-            } else {
-                self.v[0xF] = 1;
-            }
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SUB V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
-    
-        } else if fl == 0x800E { //Bitshift Vx left by 1
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.v[0xF] = self.v[reg_x] >> 7;
-            self.v[reg_x] = self.v[reg_x] << 1;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "BITSHIFT_LEFT V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
-    
-        } else if fl == 0x9000 { //Skip next instruction if Vx != Vy
-            self.pc += 2;
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            if self.v[reg_x] != self.v[reg_y] {
-                self.pc += 2;
-            }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_NOT_EQUAL V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-            }
-
-        } else if first == 0xA000 { //Set I to address NNN
-            let address = self.opcode & 0x0FFF;
-            self.i = address;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SET_I ".to_string();
-                self.debug_info.opcode_trans.push_str(&address.to_string());
-            }
-
-
-        } else if first == 0xB000 { //Jump to address V0+NNN
-            let address = self.opcode & 0x0FFF;
-            self.pc = self.v[0] as u16 + address;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "JUMP_TO_V0_ADD ".to_string();
-                self.debug_info.opcode_trans.push_str(&address.to_string());
-            }
-
-        } else if first == 0xC000 { //Set Vx to a random number & NN
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let num = (self.opcode & 0x00FF) as u8;
-            let mut rng = rand::thread_rng();
-            self.v[reg_x] = rng.gen_range(0..256) as u8 & num;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SET_TO_RANDOM_AND V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&num.to_string());
-            }
-
-        } else if first == 0xD000 { //Draw sprite
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let reg_y = ((self.opcode & 0x00F0) >> 4) as usize;
-            let height = self.opcode & 0x000F;
-            self.v[0xF] = 0;
-            *draw = true;
-
-            for y in 0..height {
-                let line = self.memory[(self.i+y) as usize];
-                for x in 0..8 {
+        if debug {
+            self.refresh_debug_info();
+        }
 
-                    if (line & (0x80 >> x)) != 0 {
+        self.execute(instruction)
+    }
 
-                        let pos = ((((y + self.v[reg_y] as u16)*64) + x + self.v[reg_x] as u16)%2048) as usize;
-                        if self.gfx[pos] == 1 {
-                            self.v[0xF] = 1;
-                        }
-                        self.gfx[pos] ^= 1;
-                    } 
-                }
-            }
+    //Copies the registers and the instruction at the program counter into the debug information
+    pub fn refresh_debug_info(&mut self) {
+        let opcode = self.opcode_at(self.pc as usize);
+        self.debug_info.opcode = opcode;
+        self.debug_info.i = self.i;
+        self.debug_info.pc = self.pc;
+        self.debug_info.delay_tmr = self.delay_tmr;
+        self.debug_info.sound_tmr = self.sound_tmr;
+        self.debug_info.sp = self.sp;
+        self.debug_info.v = self.v;
+        self.debug_info.stack = self.stack;
+        self.debug_info.keypad = self.keypad;
+        self.debug_info.opcode_trans = self.next_instruction().to_string();
+    }
 
-            self.pc += 2;
+    //Executes a decoded instruction, the program counter is only moved if the instruction succeeds
+    pub fn execute(&mut self, instruction: Instruction) -> Result<StepOutcome, CpuError> {
+        if let Some(error) = &self.halted {
+            return Err(error.clone());
+        }
 
-            if debug {
-                self.debug_info.opcode_trans = "DRAW_SPRITE_XYH V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-                self.debug_info.opcode_trans.push_str(" V");
-                self.debug_info.opcode_trans.push_str(&reg_y.to_string());
-                self.debug_info.opcode_trans.push_str(" ");
-                self.debug_info.opcode_trans.push_str(&height.to_string());
-            }
+        let mut next_pc = self.pc.wrapping_add(instruction.size());
+        let mut outcome = StepOutcome::Continue;
 
-        } else if ftl == 0xE09E { //Skip next instruction if key specified is pressed
-            self.pc += 2;
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let key = self.keypad[self.v[reg_x] as usize];
-            if key != 0 {
-                self.pc += 2;
+        match instruction {
+            Instruction::Clear => {
+                for pixel in &mut self.gfx {
+                    *pixel &= !self.planes;
+                }
+                outcome = StepOutcome::Draw;
             }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_KEY_PRESSED ".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
+            Instruction::Return => {
+                if self.sp == 0 {
+                    let error = CpuError::StackUnderflow { pc: self.pc, opcode: self.opcode };
+                    return Err(self.halt(error));
+                }
+                self.sp -= 1;
+                next_pc = self.stack[self.sp as usize].wrapping_add(2);
             }
-
-        } else if ftl == 0xE0A1 { //Skip next instruction if key specified is not pressed
-            self.pc += 2;
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let key = self.keypad[self.v[reg_x] as usize];
-            if key == 0 {
-                self.pc += 2;
+            Instruction::ScrollDown { n } => {
+                self.scroll(0, n as isize);
+                outcome = StepOutcome::Draw;
             }
-
-            if debug {
-                self.debug_info.opcode_trans = "SKIP_IF_KEY_NOT_PRESSED ".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
+            Instruction::ScrollUp { n } => {
+                self.scroll(0, -(n as isize));
+                outcome = StepOutcome::Draw;
             }
-
-
-        } else if ftl == 0xF007 { //Set Vx to delay timer
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.v[reg_x] = self.delay_tmr;
-            self.pc += 2;
-
-            if debug {
-                self.debug_info.opcode_trans = "SET_TO_DELAY_TIMER V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+                outcome = StepOutcome::Draw;
             }
-
-        } else if ftl == 0xF00A { //Keypress is waited for and stored in Vx
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            for key in 0..self.keypad.len() {
-                if self.keypad[key] != 0 {
-                    self.v[reg_x] = key as u8;
-                    self.pc += 2;
-                    break;
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+                outcome = StepOutcome::Draw;
+            }
+            Instruction::Exit => {
+                //The program counter stays on the exit instruction
+                next_pc = self.pc;
+                outcome = StepOutcome::Exit;
+            }
+            Instruction::LowRes => {
+                self.set_hires(false);
+                outcome = StepOutcome::Draw;
+            }
+            Instruction::HighRes => {
+                self.set_hires(true);
+                outcome = StepOutcome::Draw;
+            }
+            Instruction::Jump { address } => {
+                next_pc = address;
+            }
+            Instruction::Call { address } => {
+                if self.sp as usize == self.stack.len() {
+                    let error = CpuError::StackOverflow { pc: self.pc, opcode: self.opcode };
+                    return Err(self.halt(error));
                 }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                next_pc = address;
             }
-
-            if debug {
-                self.debug_info.opcode_trans = "WAIT_FOR_KEYPRESS_AND_STORE V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
+            Instruction::SkipEqualByte { x, byte } => {
+                if self.v[x as usize] == byte {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::SkipNotEqualByte { x, byte } => {
+                if self.v[x as usize] != byte {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::SkipEqual { x, y } => {
+                if self.v[x as usize] == self.v[y as usize] {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::StoreRange { x, y } => {
+                let address = self.i as usize;
+                if let Err(error) = self.check_range(address, x.max(y) as usize - x.min(y) as usize + 1) {
+                    return Err(self.halt(error));
+                }
+                for (offset, reg) in register_range(x as usize, y as usize).enumerate() {
+                    self.memory[address + offset] = self.v[reg];
+                }
+            }
+            Instruction::LoadRange { x, y } => {
+                let address = self.i as usize;
+                if let Err(error) = self.check_range(address, x.max(y) as usize - x.min(y) as usize + 1) {
+                    return Err(self.halt(error));
+                }
+                for (offset, reg) in register_range(x as usize, y as usize).enumerate() {
+                    self.v[reg] = self.memory[address + offset];
+                }
+            }
+            Instruction::SetByte { x, byte } => {
+                self.v[x as usize] = byte;
+            }
+            Instruction::AddByte { x, byte } => {
+                self.v[x as usize] = self.v[x as usize].wrapping_add(byte);
+            }
+            Instruction::Set { x, y } => {
+                self.v[x as usize] = self.v[y as usize];
+            }
+            Instruction::Or { x, y } => {
+                self.v[x as usize] |= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
+            }
+            Instruction::And { x, y } => {
+                self.v[x as usize] &= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
+            }
+            Instruction::Xor { x, y } => {
+                self.v[x as usize] ^= self.v[y as usize];
+                if self.quirks.vf_reset {
+                    self.v[0xF] = 0;
+                }
+            }
+            Instruction::Add { x, y } => {
+                let (result, carry) = self.v[x as usize].overflowing_add(self.v[y as usize]);
+                self.v[x as usize] = result;
+                self.v[0xF] = carry as u8;
+            }
+            Instruction::Sub { x, y } => {
+                let (result, borrow) = self.v[x as usize].overflowing_sub(self.v[y as usize]);
+                self.v[x as usize] = result;
+                self.v[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftRight { x, y } => {
+                //Without the shifting quirk Vy is shifted into Vx
+                let value = if self.quirks.shifting { self.v[x as usize] } else { self.v[y as usize] };
+                self.v[x as usize] = value >> 1;
+                self.v[0xF] = value & 0x1;
+            }
+            Instruction::SubReverse { x, y } => {
+                let (result, borrow) = self.v[y as usize].overflowing_sub(self.v[x as usize]);
+                self.v[x as usize] = result;
+                self.v[0xF] = !borrow as u8;
+            }
+            Instruction::ShiftLeft { x, y } => {
+                let value = if self.quirks.shifting { self.v[x as usize] } else { self.v[y as usize] };
+                self.v[x as usize] = value << 1;
+                self.v[0xF] = value >> 7;
+            }
+            Instruction::SkipNotEqual { x, y } => {
+                if self.v[x as usize] != self.v[y as usize] {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::SetI { address } => {
+                self.i = address;
+            }
+            Instruction::JumpOffset { x, address } => {
+                //With the jumping quirk this is BXNN which jumps to XNN plus VX
+                let reg = if self.quirks.jumping { x as usize } else { 0 };
+                next_pc = self.v[reg] as u16 + address;
+            }
+            Instruction::Random { x, byte } => {
+                let mut rng = rand::thread_rng();
+                self.v[x as usize] = rng.gen::<u8>() & byte;
+            }
+            Instruction::Draw { x, y, n } => {
+                //A height of 0 draws a 16x16 sprite
+                let (rows, bytes_per_row) = if n == 0 { (16, 2) } else { (n as usize, 1) };
+                let sprite_len = rows*bytes_per_row*self.planes.count_ones() as usize;
+                if let Err(error) = self.check_range(self.i as usize, sprite_len) {
+                    return Err(self.halt(error));
+                }
+                let collision = self.draw_sprite(self.v[x as usize] as usize, self.v[y as usize] as usize, rows, n == 0);
+                self.v[0xF] = collision as u8;
+                outcome = StepOutcome::Draw;
+            }
+            Instruction::SkipKeyPressed { x } => {
+                if self.keypad[(self.v[x as usize] & 0xF) as usize] != 0 {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::SkipKeyNotPressed { x } => {
+                if self.keypad[(self.v[x as usize] & 0xF) as usize] == 0 {
+                    next_pc = next_pc.wrapping_add(self.instruction_len_at(next_pc));
+                }
+            }
+            Instruction::SetILong { address } => {
+                self.i = address;
+            }
+            Instruction::SelectPlanes { n } => {
+                self.planes = n & 0x3;
+            }
+            Instruction::LoadAudio => {
+                let address = self.i as usize;
+                if let Err(error) = self.check_range(address, self.pattern.len()) {
+                    return Err(self.halt(error));
+                }
+                self.pattern.copy_from_slice(&self.memory[address..address+16]);
+            }
+            Instruction::GetDelay { x } => {
+                self.v[x as usize] = self.delay_tmr;
+            }
+            Instruction::WaitKey { x } => {
+                //The program counter stays on this instruction until a key is pressed
+                match self.keypad.iter().position(|&key| key != 0) {
+                    Some(key) => self.v[x as usize] = key as u8,
+                    None => next_pc = self.pc
+                }
+            }
+            Instruction::SetDelay { x } => {
+                self.delay_tmr = self.v[x as usize];
+            }
+            Instruction::SetSound { x } => {
+                self.sound_tmr = self.v[x as usize];
+            }
+            Instruction::AddI { x } => {
+                self.i = self.i.wrapping_add(self.v[x as usize] as u16);
             }
+            Instruction::Font { x } => {
+                self.i = (FONT_ADDRESS + (self.v[x as usize] & 0xF) as usize * 5) as u16;
+            }
+            Instruction::BigFont { x } => {
+                self.i = (BIG_FONT_ADDRESS + (self.v[x as usize] & 0xF) as usize * 10) as u16;
+            }
+            Instruction::Bcd { x } => {
+                let address = self.i as usize;
+                if let Err(error) = self.check_range(address, 3) {
+                    return Err(self.halt(error));
+                }
+                let value = self.v[x as usize];
+                self.memory[address] = value / 100;
+                self.memory[address+1] = (value % 100) / 10;
+                self.memory[address+2] = value % 10;
+            }
+            Instruction::SetPitch { x } => {
+                self.pitch = self.v[x as usize];
+            }
+            Instruction::Store { x } => {
+                let (address, x) = (self.i as usize, x as usize);
+                if let Err(error) = self.check_range(address, x + 1) {
+                    return Err(self.halt(error));
+                }
+                self.memory[address..=address+x].copy_from_slice(&self.v[..=x]);
+                if self.quirks.memory {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+            Instruction::Load { x } => {
+                let (address, x) = (self.i as usize, x as usize);
+                if let Err(error) = self.check_range(address, x + 1) {
+                    return Err(self.halt(error));
+                }
+                self.v[..=x].copy_from_slice(&self.memory[address..=address+x]);
+                if self.quirks.memory {
+                    self.i = self.i.wrapping_add(x as u16 + 1);
+                }
+            }
+            Instruction::StoreFlags { x } => {
+                let count = (x as usize + 1).min(self.rpl.len());
+                self.rpl[..count].copy_from_slice(&self.v[..count]);
+            }
+            Instruction::LoadFlags { x } => {
+                let count = (x as usize + 1).min(self.rpl.len());
+                self.v[..count].copy_from_slice(&self.rpl[..count]);
+            }
+            Instruction::Unknown { opcode } => {
+                let error = CpuError::UnknownOpcode { pc: self.pc, opcode };
+                return Err(self.halt(error));
+            }
+        }
 
-        } else if ftl == 0xF015 { //Set delay timer to Vx
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as u8;
-            self.delay_tmr = reg_x;
-            self.pc += 2;
+        self.pc = next_pc;
+        Ok(outcome)
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "SET_DELAY_TIMER V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    //The memory an instruction would access if it were executed in the current state
+    pub fn memory_access(&self, instruction: Instruction) -> Option<MemoryAccess> {
+        let address = self.i as usize;
+        match instruction {
+            Instruction::Draw { n, .. } => {
+                let (rows, bytes_per_row) = if n == 0 { (16, 2) } else { (n as usize, 1) };
+                Some(MemoryAccess::Read { address, len: rows*bytes_per_row*self.planes.count_ones() as usize })
+            }
+            Instruction::LoadAudio => Some(MemoryAccess::Read { address, len: self.pattern.len() }),
+            Instruction::Load { x } => Some(MemoryAccess::Read { address, len: x as usize + 1 }),
+            Instruction::LoadRange { x, y } => Some(MemoryAccess::Read { address, len: x.max(y) as usize - x.min(y) as usize + 1 }),
+            Instruction::Bcd { .. } => Some(MemoryAccess::Write { address, len: 3 }),
+            Instruction::Store { x } => Some(MemoryAccess::Write { address, len: x as usize + 1 }),
+            Instruction::StoreRange { x, y } => Some(MemoryAccess::Write { address, len: x.max(y) as usize - x.min(y) as usize + 1 }),
+            _ => None
+        }
+    }
 
-        } else if ftl == 0xF018 { //Set sound timer to Vx
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as u8;
-            self.sound_tmr = reg_x;
-            self.pc += 2;
+    //The instruction at the program counter, which is the next one to be executed
+    pub fn next_instruction(&self) -> Instruction {
+        let pc = self.pc as usize;
+        match decode_at(&self.memory[pc..]) {
+            Some((instruction, _)) => instruction,
+            None => Instruction::Unknown { opcode: self.opcode_at(pc) }
+        }
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "SET_SOUND_TIMER V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    pub fn opcode_at(&self, address: usize) -> u16 {
+        if address + 1 < MEMORY_SIZE {
+            ((self.memory[address] as u16) << 8) | self.memory[address+1] as u16
+        } else {
+            self.opcode
+        }
+    }
 
-        } else if ftl == 0xF01E { //Add Vx to I
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.i += self.v[reg_x] as u16;
-            self.pc += 2;
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "ADD_TO_I V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    pub fn i(&self) -> u16 {
+        self.i
+    }
 
-        } else if ftl == 0xF029 { //Set I to location of a character sprite
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.i = (self.v[reg_x] as u16) * 5;
-            self.pc += 2;
+    pub fn register(&self, reg: usize) -> u8 {
+        self.v[reg]
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "SET_I_TO_SPRITE ".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
 
-        } else if ftl == 0xF033 { //Stores BCD Representation at address at I
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            self.memory[self.i as usize] = self.v[reg_x] / 100;
-            self.memory[(self.i+1) as usize] = (self.v[reg_x] % 100) / 10;
-            self.memory[(self.i+2) as usize] = (self.v[reg_x] % 100) % 10;
-            self.pc += 2;
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_tmr
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "STORE_BCD_AT_I V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_tmr
+    }
 
-        } else if ftl == 0xF055 { //Store V0 to VX in memory starting at I
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let mut address = self.i as usize;
-            for num in 0..=reg_x {
-                self.memory[address] = self.v[num];
-                address += 1;
-            }
-            self.pc += 2;
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "STORE_V0_TO_VX_AT_I V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    //Setters used by the debugger to change the machine state
+    pub fn set_register(&mut self, reg: usize, value: u8) {
+        self.v[reg] = value;
+    }
 
-        } else if ftl == 0xF065 { //Load V0 to VX from memory starting at I
-            let reg_x = ((self.opcode & 0x0F00) >> 8) as usize;
-            let mut address = self.i as usize;
-            for num in 0..=reg_x {
-                self.v[num] = self.memory[address];
-                address += 1;
-            }
-            self.pc += 2;
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
 
-            if debug {
-                self.debug_info.opcode_trans = "LOAD_V0_TO_VX_AT_I V".to_string();
-                self.debug_info.opcode_trans.push_str(&reg_x.to_string());
-            }
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
 
-        } else {
-            panic!("Unknown opcode: [{:X}]",self.opcode)
-        }
-        
-        //Increment the timers if they are running
+    //The stack pointer can not be moved past the top of the stack
+    pub fn set_sp(&mut self, sp: u16) {
+        self.sp = sp.min(self.stack.len() as u16);
+    }
+
+    pub fn poke(&mut self, address: usize, value: u8) {
+        self.memory[address] = value;
+    }
+
+    //Decrements the timers if they are running, called at 60 Hz independently of the instruction rate
+    pub fn tick_timers(&mut self) {
         if self.delay_tmr > 0 {
             self.delay_tmr-=1;
         }
         if self.sound_tmr > 0 {
             self.sound_tmr-=1;
-            if self.sound_tmr == 0 {
-                *beep = true;
-            }
         }
+    }
+
+    //The audio pattern is only played while the sound timer is running
+    pub fn sound_playing(&self) -> bool {
+        self.sound_tmr > 0
+    }
+
+    pub fn audio_pattern(&self) -> [u8; 16] {
+        self.pattern
+    }
 
+    pub fn audio_pitch(&self) -> u8 {
+        self.pitch
     }
 
-    pub fn mem_dump(self)-> [u8; 4096] {
-        return self.memory;
+    pub fn mem_dump(self)-> Vec<u8> {
+        self.memory
     }
 
+    //Writes the machine state for a save state, the quirks are part of the configuration rather than the state
+    pub fn write_state(&self, state: &mut Vec<u8>) {
+        state.extend_from_slice(&self.opcode.to_be_bytes());
+        state.extend_from_slice(&self.v);
+        state.extend_from_slice(&self.i.to_be_bytes());
+        state.extend_from_slice(&self.pc.to_be_bytes());
+        state.push(self.hires as u8);
+        state.push(self.planes);
+        state.push(self.delay_tmr);
+        state.push(self.sound_tmr);
+        state.extend_from_slice(&self.pattern);
+        state.push(self.pitch);
+        for address in &self.stack {
+            state.extend_from_slice(&address.to_be_bytes());
+        }
+        state.extend_from_slice(&self.sp.to_be_bytes());
+        state.extend_from_slice(&self.rpl);
+        state.extend_from_slice(&self.keypad);
+        state.extend_from_slice(&self.memory);
+        state.extend_from_slice(&self.gfx);
+    }
+
+    //Reads the machine state written by write_state, returns None if it is truncated or invalid
+    pub fn read_state(&mut self, state: &mut StateReader) -> Option<()> {
+        self.opcode = state.u16()?;
+        self.v.copy_from_slice(state.bytes(16)?);
+        self.i = state.u16()?;
+        self.pc = state.u16()?;
+        self.hires = state.u8()? != 0;
+        self.planes = state.u8()?;
+        self.delay_tmr = state.u8()?;
+        self.sound_tmr = state.u8()?;
+        self.pattern.copy_from_slice(state.bytes(16)?);
+        self.pitch = state.u8()?;
+        for address in self.stack.iter_mut() {
+            *address = state.u16()?;
+        }
+        self.sp = state.u16()?;
+        if self.sp as usize > self.stack.len() {
+            return None;
+        }
+        self.rpl.copy_from_slice(state.bytes(16)?);
+        self.keypad.copy_from_slice(state.bytes(16)?);
+        self.memory.copy_from_slice(state.bytes(MEMORY_SIZE)?);
+        let gfx_len = self.width()*self.height();
+        self.gfx = state.bytes(gfx_len)?.to_vec();
+
+        //The restored machine starts running again even if it was halted when the state was loaded
+        self.halted = None;
+        self.refresh_debug_info();
+        Some(())
+    }
+
+}
+
+//Iterates over the registers from X to Y, counting down when X is greater than Y
+fn register_range(reg_x: usize, reg_y: usize) -> Box<dyn Iterator<Item = usize>> {
+    if reg_x <= reg_y {
+        Box::new(reg_x..=reg_y)
+    } else {
+        Box::new((reg_y..=reg_x).rev())
+    }
 }
 
-pub fn init(rom: [u8; 3584]) -> Chip8 {
-   Chip8{..Chip8::init(rom)}
+pub fn init(rom: Vec<u8>, quirks: Quirks) -> Chip8 {
+   Chip8{..Chip8::init(rom, quirks)}
 }
 
 
@@ -0,0 +1,97 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::chip8::MEMORY_SIZE;
+use crate::debugger::{parse_number, parse_register, Breakpoint, WatchKind, Watchpoint};
+
+//Registers that can be changed with the set command
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Target {
+    V(u8),
+    I,
+    Pc
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    Break(Breakpoint),
+    Watch(Watchpoint),
+    Set { target: Target, value: u16 },
+    Poke { address: u16, value: u8 },
+    Mem { address: u16, len: usize },
+    Continue,
+    Step(u32),
+    Reset,
+    Help
+}
+
+pub const HELP: [&str; 3] = [
+    "break <address | opcode pattern | condition>   watch[-read|-write] <range>",
+    "set <v0-vf | i | pc> <value>   poke <address> <byte>   mem <address> [length]",
+    "continue   step [count]   reset   help"
+];
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let (name, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args: Vec<&str> = args.split_whitespace().collect();
+        match (name, args.as_slice()) {
+            //Conditions may be written with spaces so the rest of the line is the breakpoint
+            ("break", [_, ..]) => Ok(Command::Break(Breakpoint::parse(&args.join(" "))?)),
+            ("watch", [range]) => Ok(Command::Watch(Watchpoint::parse(range, WatchKind::Access)?)),
+            ("watch-read", [range]) => Ok(Command::Watch(Watchpoint::parse(range, WatchKind::Read)?)),
+            ("watch-write", [range]) => Ok(Command::Watch(Watchpoint::parse(range, WatchKind::Write)?)),
+            ("set", [target, value]) => {
+                let target = match *target {
+                    "i" | "I" => Target::I,
+                    "pc" | "PC" => Target::Pc,
+                    reg => Target::V(parse_register(reg).ok_or(format!("Register not recognized: {}", reg))?)
+                };
+                let value = number(value)?;
+                if matches!(target, Target::V(_)) && value > 0xFF {
+                    return Err(format!("Value does not fit in a register: {}", value));
+                }
+                Ok(Command::Set { target, value })
+            }
+            ("poke", [address, value]) => {
+                let value = number(value)?;
+                if value > 0xFF {
+                    return Err(format!("Value does not fit in a byte: {}", value));
+                }
+                Ok(Command::Poke { address: number(address)?, value: value as u8 })
+            }
+            ("mem", [address]) => Ok(Command::Mem { address: number(address)?, len: 16 }),
+            ("mem", [address, len]) => Ok(Command::Mem { address: number(address)?, len: number(len)? as usize }),
+            ("continue", []) | ("c", []) => Ok(Command::Continue),
+            ("step", []) | ("s", []) => Ok(Command::Step(1)),
+            ("step", [count]) | ("s", [count]) => Ok(Command::Step(number(count)? as u32)),
+            ("reset", []) => Ok(Command::Reset),
+            ("help", []) => Ok(Command::Help),
+            _ => Err(format!("Command not recognized: {} (type help for a list of commands)", line))
+        }
+    }
+}
+
+fn number(text: &str) -> Result<u16, String> {
+    parse_number(text).ok_or(format!("Number not recognized: {}", text))
+}
+
+//Formats memory as lines of 16 bytes, stopping at the end of memory
+pub fn hex_dump(memory: &[u8], address: u16, len: usize) -> Vec<String> {
+    let start = address as usize;
+    let end = (start + len).min(MEMORY_SIZE);
+    (start..end).step_by(16).map(|line| {
+        let bytes: Vec<String> = memory[line..(line + 16).min(end)].iter().map(|byte| format!("{:02X}", byte)).collect();
+        format!("{:#06X}  {}", line, bytes.join(" "))
+    }).collect()
+}
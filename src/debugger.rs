@@ -0,0 +1,338 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+
+use crate::chip8::{Chip8, MemoryAccess, MEMORY_SIZE};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual
+}
+
+//Operators in the order they are searched for, so that <= is not mistaken for <
+const COMPARISONS: [(&str, Comparison); 6] = [
+    ("==", Comparison::Equal),
+    ("!=", Comparison::NotEqual),
+    ("<=", Comparison::LessOrEqual),
+    (">=", Comparison::GreaterOrEqual),
+    ("<", Comparison::Less),
+    (">", Comparison::Greater)
+];
+
+impl Comparison {
+    fn holds(self, left: u16, right: u16) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::Greater => left > right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::GreaterOrEqual => left >= right
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        COMPARISONS.iter().find(|(_, comparison)| *comparison == self).unwrap().0
+    }
+}
+
+//Register compared by a condition breakpoint
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Register {
+    V(u8),
+    I
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(reg) => write!(f, "V{:X}", reg),
+            Register::I => write!(f, "I")
+        }
+    }
+}
+
+//Opcode pattern such as DXYN, where X, Y, N and K are wildcard nibbles
+#[derive(Clone, PartialEq, Debug)]
+pub struct OpcodePattern {
+    pattern: u16,
+    mask: u16,
+    text: String
+}
+
+impl OpcodePattern {
+    pub fn parse(text: &str) -> Result<OpcodePattern, String> {
+        let text = text.trim();
+        if text.len() != 4 {
+            return Err(format!("Opcode pattern not recognized: {}", text));
+        }
+        let mut pattern = 0;
+        let mut mask = 0;
+        for c in text.chars() {
+            pattern <<= 4;
+            mask <<= 4;
+            if let Some(digit) = c.to_digit(16) {
+                pattern |= digit as u16;
+                mask |= 0xF;
+            } else if !"XYNKxynk".contains(c) {
+                return Err(format!("Opcode pattern not recognized: {}", text));
+            }
+        }
+        Ok(OpcodePattern { pattern, mask, text: text.to_uppercase() })
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.pattern
+    }
+}
+
+impl fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum Breakpoint {
+    //Stops before the instruction at an address
+    Pc(u16),
+    //Stops before any instruction matching the opcode pattern
+    Opcode(OpcodePattern),
+    //Stops when the condition becomes true
+    Condition { register: Register, comparison: Comparison, value: u16 }
+}
+
+impl Breakpoint {
+    /*Parses a breakpoint: an address such as 0x234, an opcode pattern such as DXYN or 1234 where X, Y, N and K are
+    wildcards, or a register condition such as V3 == 0x10*/
+    pub fn parse(text: &str) -> Result<Breakpoint, String> {
+        let text = text.trim();
+        if let Some((symbol, comparison)) = COMPARISONS.iter().find(|(symbol, _)| text.contains(symbol)) {
+            let (register, value) = text.split_once(symbol).unwrap();
+            let register = match register.trim() {
+                "I" | "i" => Register::I,
+                reg => match parse_register(reg) {
+                    Some(reg) => Register::V(reg),
+                    None => return Err(format!("Register not recognized: {}", reg))
+                }
+            };
+            let value = match parse_number(value.trim()) {
+                Some(value) => value,
+                None => return Err(format!("Value not recognized: {}", value.trim()))
+            };
+            return Ok(Breakpoint::Condition { register, comparison: *comparison, value });
+        }
+
+        //Addresses are written in hex with a 0x prefix so that patterns made only of digits such as 1234 stay patterns
+        if text.starts_with("0x") || text.starts_with("0X") {
+            return match parse_number(text) {
+                Some(address) => Ok(Breakpoint::Pc(address)),
+                None => Err(format!("Address not recognized: {}", text))
+            };
+        }
+
+        if text.len() == 4 {
+            return Ok(Breakpoint::Opcode(OpcodePattern::parse(text)?));
+        }
+
+        if text.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Breakpoint not recognized: {} (addresses need a 0x prefix)", text));
+        }
+
+        Err(format!("Breakpoint not recognized: {} (expected an address, opcode pattern or register condition)", text))
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(address) => write!(f, "breakpoint at {:#06X}", address),
+            Breakpoint::Opcode(pattern) => write!(f, "breakpoint on opcode {}", pattern),
+            Breakpoint::Condition { register, comparison, value } =>
+                write!(f, "breakpoint on {} {} {:#04X}", register, comparison.symbol(), value)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WatchKind {
+    Read,
+    Write,
+    Access
+}
+
+//Stops before an instruction that reads or writes memory in an inclusive address range
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Watchpoint {
+    pub start: usize,
+    pub end: usize,
+    pub kind: WatchKind
+}
+
+impl Watchpoint {
+    pub fn parse(range: &str, kind: WatchKind) -> Result<Watchpoint, String> {
+        let (start, end) = parse_range(range)?;
+        Ok(Watchpoint { start, end, kind })
+    }
+
+    fn matches(&self, access: MemoryAccess) -> bool {
+        let (address, len, kind_matches) = match access {
+            MemoryAccess::Read { address, len } => (address, len, self.kind != WatchKind::Write),
+            MemoryAccess::Write { address, len } => (address, len, self.kind != WatchKind::Read)
+        };
+        kind_matches && len > 0 && address <= self.end && address + len > self.start
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            WatchKind::Read => "read",
+            WatchKind::Write => "write",
+            WatchKind::Access => "access"
+        };
+        write!(f, "{} watchpoint on {:#06X}-{:#06X}", kind, self.start, self.end)
+    }
+}
+
+//The breakpoint or watchpoint that stopped the emulation, and the program counter it stopped at
+#[derive(Clone, PartialEq, Debug)]
+pub enum Hit {
+    Breakpoint { breakpoint: Breakpoint, pc: u16 },
+    Watchpoint { watchpoint: Watchpoint, pc: u16 }
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Hit::Breakpoint { breakpoint, pc } => write!(f, "Hit {} at {:#06X}", breakpoint, pc),
+            Hit::Watchpoint { watchpoint, pc } => write!(f, "Hit {} at {:#06X}", watchpoint, pc)
+        }
+    }
+}
+
+pub struct Debugger {
+    //Each breakpoint is kept with whether its condition held at the last check
+    breakpoints: Vec<(Breakpoint, bool)>,
+    watchpoints: Vec<Watchpoint>,
+    resuming: bool
+}
+
+impl Debugger {
+    pub fn new() -> Debugger {
+        Debugger {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            resuming: false
+        }
+    }
+
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        self.breakpoints.push((breakpoint, false));
+    }
+
+    //Removes one copy of a breakpoint, returns false if there was no such breakpoint
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        match self.breakpoints.iter().position(|(existing, _)| existing == breakpoint) {
+            Some(index) => {
+                self.breakpoints.remove(index);
+                true
+            }
+            None => false
+        }
+    }
+
+    pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) {
+        self.watchpoints.push(watchpoint);
+    }
+
+    //Lets the instruction the emulation stopped at run when it resumes instead of stopping at it again
+    pub fn resume(&mut self) {
+        self.resuming = true;
+    }
+
+    //Checks the instruction about to be executed, returns the first breakpoint or watchpoint it hits
+    pub fn check(&mut self, emu: &Chip8) -> Option<Hit> {
+        let resuming = self.resuming;
+        self.resuming = false;
+        let pc = emu.pc();
+        let instruction = emu.next_instruction();
+        let opcode = emu.opcode_at(pc as usize);
+        let mut hit = None;
+
+        for (breakpoint, held) in self.breakpoints.iter_mut() {
+            let fired = match breakpoint {
+                Breakpoint::Pc(address) => !resuming && pc == *address,
+                Breakpoint::Opcode(pattern) => !resuming && pattern.matches(opcode),
+                Breakpoint::Condition { register, comparison, value } => {
+                    //Conditions only fire when they become true, otherwise they would stop every instruction
+                    let left = match register {
+                        Register::V(reg) => emu.register(*reg as usize) as u16,
+                        Register::I => emu.i()
+                    };
+                    let holds = comparison.holds(left, *value);
+                    let became_true = holds && !*held;
+                    *held = holds;
+                    became_true
+                }
+            };
+            if fired && hit.is_none() {
+                hit = Some(Hit::Breakpoint { breakpoint: breakpoint.clone(), pc });
+            }
+        }
+        if hit.is_some() || resuming {
+            return hit;
+        }
+
+        let access = emu.memory_access(instruction)?;
+        self.watchpoints.iter()
+            .find(|watchpoint| watchpoint.matches(access))
+            .map(|watchpoint| Hit::Watchpoint { watchpoint: *watchpoint, pc })
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Debugger {
+        Debugger::new()
+    }
+}
+
+//Parses an inclusive address range such as 0x300-0x30F, or a single address
+pub fn parse_range(range: &str) -> Result<(usize, usize), String> {
+    let (start, end) = range.split_once('-').unwrap_or((range, range));
+    match (parse_number(start.trim()), parse_number(end.trim())) {
+        (Some(start), Some(end)) if start <= end && (end as usize) < MEMORY_SIZE => Ok((start as usize, end as usize)),
+        _ => Err(format!("Address range not recognized: {}", range))
+    }
+}
+
+//Numbers are hexadecimal with a 0x prefix, or decimal
+pub fn parse_number(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok()
+    }
+}
+
+pub fn parse_register(text: &str) -> Option<u8> {
+    let reg = text.strip_prefix('V').or_else(|| text.strip_prefix('v'))?;
+    if reg.len() != 1 {
+        return None;
+    }
+    reg.chars().next()?.to_digit(16).map(|reg| reg as u8)
+}
@@ -0,0 +1,229 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::chip8::{MEMORY_SIZE, PROGRAM_START};
+use crate::instruction::{decode_at, Instruction};
+
+//What each byte of the ROM was found to be while following the code
+#[derive(Clone, Copy, PartialEq)]
+enum ByteKind {
+    Data,
+    Code,
+    CodeOperand, //The second half of an instruction or the address following F000
+    Sprite
+}
+
+//Kinds of labels, a jump target that is also called is labelled as a subroutine
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+enum LabelKind {
+    Data,
+    Sprite,
+    Jump,
+    Subroutine
+}
+
+pub struct Disassembly {
+    rom: Vec<u8>,
+    kinds: Vec<ByteKind>,
+    labels: BTreeMap<u16, LabelKind>
+}
+
+/*Disassembles a ROM by following every path through the code from the start of the program, so that sprites and
+other data mixed in with the code are not shown as instructions. Jumps using BNNN can not be followed because their
+targets depend on V0*/
+pub fn disassemble(rom: &[u8]) -> Disassembly {
+    let end = PROGRAM_START + rom.len();
+    let mut disassembly = Disassembly {
+        rom: rom.to_vec(),
+        kinds: vec![ByteKind::Data; rom.len()],
+        labels: BTreeMap::new()
+    };
+    let mut visited = BTreeSet::new();
+
+    //Each path is followed with the value of I last set on it, so a draw can mark the sprite it uses
+    let mut paths: Vec<(usize, Option<u16>)> = vec![(PROGRAM_START, None)];
+    while let Some((start, mut i)) = paths.pop() {
+        //Targets outside the ROM, such as machine code routines below the program, are labelled but not followed
+        let mut address = start;
+        while address >= PROGRAM_START && address + 1 < end && visited.insert(address) {
+            let (instruction, len) = match disassembly.decode(address) {
+                Some((Instruction::Unknown { .. }, _)) | None => break,
+                Some(decoded) => decoded
+            };
+            let next = address + len;
+            disassembly.mark(address, ByteKind::Code);
+            for operand in address+1..next {
+                disassembly.mark(operand, ByteKind::CodeOperand);
+            }
+
+            match instruction {
+                Instruction::Jump { address: target } => {
+                    disassembly.label(target, LabelKind::Jump);
+                    paths.push((target as usize, i));
+                    break;
+                }
+                Instruction::Call { address: target } => {
+                    disassembly.label(target, LabelKind::Subroutine);
+                    paths.push((target as usize, i));
+                }
+                Instruction::SkipEqualByte { .. } | Instruction::SkipNotEqualByte { .. } |
+                Instruction::SkipEqual { .. } | Instruction::SkipNotEqual { .. } |
+                Instruction::SkipKeyPressed { .. } | Instruction::SkipKeyNotPressed { .. } if next + 1 < end => {
+                    //Both the skipped instruction and the one after it can run
+                    let skipped = disassembly.decode(next).map_or(2, |(_, len)| len);
+                    paths.push((next + skipped, i));
+                }
+                Instruction::SetI { address: target } | Instruction::SetILong { address: target } => {
+                    disassembly.label(target, LabelKind::Data);
+                    i = Some(target);
+                }
+                Instruction::Draw { n, .. } => {
+                    if let Some(sprite) = i {
+                        //16x16 sprites are 32 bytes, plane selection is not followed so XO-CHIP sprites may be cut short
+                        let len = if n == 0 { 32 } else { n as usize };
+                        disassembly.label(sprite, LabelKind::Sprite);
+                        for byte in sprite as usize..(sprite as usize + len).min(MEMORY_SIZE) {
+                            disassembly.mark(byte, ByteKind::Sprite);
+                        }
+                    }
+                }
+                Instruction::AddI { .. } | Instruction::Font { .. } | Instruction::BigFont { .. } |
+                Instruction::Store { .. } | Instruction::Load { .. } => {
+                    //I no longer points to a known address
+                    i = None;
+                }
+                Instruction::Return | Instruction::Exit | Instruction::JumpOffset { .. } => break,
+                _ => {}
+            }
+            address = next;
+        }
+    }
+
+    disassembly
+}
+
+impl Disassembly {
+    fn byte(&self, address: usize) -> u8 {
+        self.rom[address - PROGRAM_START]
+    }
+
+    fn decode(&self, address: usize) -> Option<(Instruction, usize)> {
+        decode_at(&self.rom[address - PROGRAM_START..])
+    }
+
+    //Code takes priority over sprites, which take priority over plain data
+    fn mark(&mut self, address: usize, kind: ByteKind) {
+        if address < PROGRAM_START || address >= PROGRAM_START + self.rom.len() {
+            return;
+        }
+        let current = &mut self.kinds[address - PROGRAM_START];
+        if *current == ByteKind::Data || (*current == ByteKind::Sprite && kind != ByteKind::Data) {
+            *current = kind;
+        }
+    }
+
+    fn label(&mut self, address: u16, kind: LabelKind) {
+        let label = self.labels.entry(address).or_insert(kind);
+        if kind > *label {
+            *label = kind;
+        }
+    }
+
+    fn label_name(&self, address: u16) -> Option<String> {
+        self.labels.get(&address).map(|kind| {
+            let prefix = match kind {
+                LabelKind::Data => "data",
+                LabelKind::Sprite => "sprite",
+                LabelKind::Jump => "label",
+                LabelKind::Subroutine => "sub"
+            };
+            format!("{}_{:03X}", prefix, address)
+        })
+    }
+
+    //Label of the address an instruction refers to, shown as a comment after the mnemonic
+    fn target_comment(&self, instruction: Instruction) -> String {
+        let target = match instruction {
+            Instruction::Jump { address } | Instruction::Call { address } |
+            Instruction::SetI { address } | Instruction::SetILong { address } => Some(address),
+            _ => None
+        };
+        match target.and_then(|address| self.label_name(address)) {
+            Some(name) => format!("; {}", name),
+            None => "".to_string()
+        }
+    }
+
+    //Annotated listing of the address, raw bytes and mnemonic of every instruction and data byte
+    pub fn listing(&self) -> String {
+        let mut listing = String::new();
+        let end = PROGRAM_START + self.rom.len();
+        let mut address = PROGRAM_START;
+
+        while address < end {
+            if let Some(name) = self.label_name(address as u16) {
+                listing.push_str(&format!("\n{}:\n", name));
+            }
+            match self.kinds[address - PROGRAM_START] {
+                ByteKind::Code => {
+                    let (instruction, len) = self.decode(address).unwrap();
+                    let raw = raw_words(&self.rom[address - PROGRAM_START..address - PROGRAM_START + len]);
+                    let line = format!("{:#06X}  {:<9}  {:<20}{}", address, raw, instruction.to_string(),
+                        self.target_comment(instruction));
+                    listing.push_str(line.trim_end());
+                    listing.push('\n');
+                    address += len;
+                }
+                ByteKind::Sprite => {
+                    let byte = self.byte(address);
+                    let pixels: String = (0..8).map(|bit| if byte & (0x80 >> bit) != 0 { '#' } else { '.' }).collect();
+                    let mnemonic = format!("DB {:#04X}", byte);
+                    listing.push_str(&format!("{:#06X}  {:02X}         {:<20}; {}\n", address, byte, mnemonic, pixels));
+                    address += 1;
+                }
+                ByteKind::Data | ByteKind::CodeOperand => {
+                    //Plain data is grouped into rows of up to 8 bytes, split at labels and code
+                    let mut row = Vec::new();
+                    while address < end && row.len() < 8 &&
+                        matches!(self.kinds[address - PROGRAM_START], ByteKind::Data | ByteKind::CodeOperand) &&
+                        (row.is_empty() || self.label_name(address as u16).is_none()) {
+                        row.push(self.byte(address));
+                        address += 1;
+                    }
+                    let start = address - row.len();
+                    let raw: Vec<String> = row.iter().map(|byte| format!("{:02X}", byte)).collect();
+                    let data: Vec<String> = row.iter().map(|byte| format!("{:#04X}", byte)).collect();
+                    listing.push_str(&format!("{:#06X}  {:<9}  DB {}\n", start, raw.join(" "), data.join(", ")));
+                }
+            }
+        }
+
+        listing
+    }
+}
+
+//Disassembles the instruction at an address in memory, returns the line and the length of the instruction
+pub fn instruction_line(memory: &[u8], address: usize) -> (String, usize) {
+    match memory.get(address..).and_then(decode_at) {
+        Some((instruction, len)) =>
+            (format!("{:#06X}  {:<9}  {}", address, raw_words(&memory[address..address+len]), instruction), len),
+        None => (format!("{:#06X}", address), 2)
+    }
+}
+
+//The bytes of an instruction in hex, one group for the opcode and one for the address following F000
+fn raw_words(bytes: &[u8]) -> String {
+    let words: Vec<String> = bytes.chunks(2).map(|word| word.iter().map(|byte| format!("{:02X}", byte)).collect()).collect();
+    words.join(" ")
+}
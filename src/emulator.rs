@@ -10,16 +10,44 @@ for more details. You should have received a copy of the GNU Affero General Publ
 not, see <https://www.gnu.org/licenses/>.
 */
 
-use crate::chip8;
+use chip8::{frontend, Chip8, StepOutcome};
+use chip8::console::{self, Command, Target};
+use chip8::debugger::{self, Breakpoint, Debugger, OpcodePattern, WatchKind, Watchpoint};
+use chip8::filter::{self, DisplayFilter, FilterMode};
+use chip8::gdb::{GdbAction, GdbStub};
+use chip8::headless::{self, StopReason};
+use chip8::quirks::{self, Quirks};
+use chip8::rewind::{Rewind, SnapshotKind};
+use chip8::savestate;
+use chip8::trace::{Trace, TraceFormat};
+use chip8::{Audio, Display};
 use crate::io;
+use crate::palette::{self, Palette};
+use crate::render::{self, RenderMode};
 use std::env;
+use std::fs;
 use std::time;
 use std::thread::sleep;
 use std::time::Duration;
 
+//The screen is drawn and the timers count down at 60 Hz
+const FPS: u32 = 60;
+
+//CPU speed when not set on the command line
+const DEFAULT_CPU_HZ: f64 = 500.0;
+
+//Emulation speed multipliers of the turbo and slow motion hotkeys
+const TURBO_SPEED: f64 = 4.0;
+const SLOW_MOTION_SPEED: f64 = 0.25;
+
+//If the emulator falls this many frames behind it stops trying to catch up
+const MAX_FRAMES_BEHIND: u32 = 5;
+
+//Length of a headless run when neither a number of frames nor cycles is given, 10 seconds
+const DEFAULT_HEADLESS_FRAMES: u64 = 600;
+
 /*TODO:
 Configuration file
-Super Chip implementation
 */
 
 pub fn emulator_loop() {
@@ -30,33 +58,212 @@ pub fn emulator_loop() {
     }
     let mut debug = false;
     let mut step = false;
-    for num_arg in 2..args.len() {
-        let arg = &args[num_arg];
+    let mut quirks = Quirks::default();
+    let mut quirk_overrides = Vec::new();
+    let mut cpu_hz = DEFAULT_CPU_HZ;
+    let mut slot = 0;
+    let mut load_slot = None;
+    let mut save_slot_on_exit = None;
+    let mut debugger = Debugger::new();
+    let mut gdb_port = None;
+    let mut trace_path = None;
+    let mut headless = false;
+    let mut frame_limit = None;
+    let mut cycle_limit = None;
+    let mut key_script = Vec::new();
+    let mut output_path = None;
+    let mut screenshot_path = None;
+    let mut trace_format = TraceFormat::Text;
+    let mut trace_range = None;
+    let mut trace_patterns = Vec::new();
+    let mut render_mode = RenderMode::Ascii;
+    let mut filter_mode = FilterMode::None;
+    let mut palette = Palette::default();
+    let mut color_overrides = Vec::new();
+    let mut options = args[2..].iter();
+    while let Some(arg) = options.next() {
         if arg == "-debug"{
             debug = true;
         } else if arg == "-step"{
             step = true;
+        } else if arg == "-quirks"{
+            let profile = options.next().expect("Error: -quirks needs a profile name");
+            quirks = match Quirks::profile(profile) {
+                Some(quirks) => quirks,
+                None => panic!("Quirks profile not recognized: {} (expected one of {})",profile,quirks::PROFILES.join(", "))
+            };
+        } else if arg == "-render"{
+            let mode = options.next().expect("Error: -render needs a render mode");
+            render_mode = RenderMode::parse(mode).unwrap_or_else(|| panic!("Render mode not recognized: {} (expected one of {})",mode,render::RENDER_MODES.join(", ")));
+        } else if arg == "-filter"{
+            let mode = options.next().expect("Error: -filter needs a filter mode");
+            filter_mode = FilterMode::parse(mode).unwrap_or_else(|| panic!("Filter mode not recognized: {} (expected one of {})",mode,filter::FILTER_MODES.join(", ")));
+        } else if arg == "-theme"{
+            let theme = options.next().expect("Error: -theme needs a theme name");
+            palette = Palette::theme(theme).unwrap_or_else(|| panic!("Theme not recognized: {} (expected one of {})",theme,palette::THEMES.join(", ")));
+        } else if arg == "-bg" || arg == "-fg"{
+            let color = options.next().unwrap_or_else(|| panic!("Error: {} needs a colour",arg));
+            color_overrides.push((if arg == "-bg" { 0 } else { 1 }, color.as_str()));
+        } else if arg == "-palette"{
+            let colors = options.next().expect("Error: -palette needs up to four comma separated colours");
+            for (planes, color) in colors.split(',').enumerate() {
+                if planes > 3 {
+                    panic!("Palette has more than four colours: {}",colors);
+                }
+                color_overrides.push((planes, color));
+            }
+        } else if arg == "-ipf"{
+            let ipf = options.next().expect("Error: -ipf needs a number of instructions per frame");
+            cpu_hz = match ipf.parse::<u32>() {
+                Ok(ipf) if ipf > 0 => (ipf*FPS) as f64,
+                _ => panic!("Instructions per frame not recognized: {}",ipf)
+            };
+        } else if arg == "-hz"{
+            let hz = options.next().expect("Error: -hz needs a CPU speed in Hz");
+            cpu_hz = match hz.parse::<f64>() {
+                Ok(hz) if hz > 0.0 => hz,
+                _ => panic!("CPU speed not recognized: {}",hz)
+            };
+        } else if arg == "-slot"{
+            slot = parse_slot(options.next().expect("Error: -slot needs a save state slot number"));
+        } else if arg == "-load-state"{
+            load_slot = Some(parse_slot(options.next().expect("Error: -load-state needs a save state slot number")));
+        } else if arg == "-save-state"{
+            save_slot_on_exit = Some(parse_slot(options.next().expect("Error: -save-state needs a save state slot number")));
+        } else if arg == "-break"{
+            let breakpoint = options.next().expect("Error: -break needs an address, opcode pattern or register condition");
+            debugger.add_breakpoint(Breakpoint::parse(breakpoint).unwrap_or_else(|error| panic!("{}",error)));
+        } else if arg == "-watch" || arg == "-watch-read" || arg == "-watch-write"{
+            let kind = match arg.as_str() {
+                "-watch-read" => WatchKind::Read,
+                "-watch-write" => WatchKind::Write,
+                _ => WatchKind::Access
+            };
+            let range = options.next().unwrap_or_else(|| panic!("Error: {} needs an address range",arg));
+            debugger.add_watchpoint(Watchpoint::parse(range, kind).unwrap_or_else(|error| panic!("{}",error)));
+        } else if arg == "-gdb" || arg == "--gdb"{
+            let port = options.next().expect("Error: -gdb needs a port number");
+            gdb_port = match port.parse::<u16>() {
+                Ok(port) => Some(port),
+                _ => panic!("Port not recognized: {}",port)
+            };
+        } else if arg == "-trace" || arg == "--trace"{
+            trace_path = Some(options.next().expect("Error: -trace needs a file to write the trace to"));
+        } else if arg == "-trace-format"{
+            let format = options.next().expect("Error: -trace-format needs a format, text or binary");
+            trace_format = TraceFormat::parse(format).unwrap_or_else(|| panic!("Trace format not recognized: {} (expected text or binary)",format));
+        } else if arg == "-trace-range"{
+            let range = options.next().expect("Error: -trace-range needs an address range");
+            trace_range = Some(debugger::parse_range(range).unwrap_or_else(|error| panic!("{}",error)));
+        } else if arg == "-trace-opcode"{
+            let pattern = options.next().expect("Error: -trace-opcode needs an opcode pattern");
+            trace_patterns.push(OpcodePattern::parse(pattern).unwrap_or_else(|error| panic!("{}",error)));
+        } else if arg == "-headless" || arg == "--headless"{
+            headless = true;
+        } else if arg == "-frames"{
+            let frames = options.next().expect("Error: -frames needs a number of frames");
+            frame_limit = Some(frames.parse::<u64>().unwrap_or_else(|_| panic!("Number of frames not recognized: {}",frames)));
+        } else if arg == "-cycles"{
+            let cycles = options.next().expect("Error: -cycles needs a number of cycles");
+            cycle_limit = Some(cycles.parse::<u64>().unwrap_or_else(|_| panic!("Number of cycles not recognized: {}",cycles)));
+        } else if arg == "-keys"{
+            let path = options.next().expect("Error: -keys needs a key script file");
+            let script = fs::read_to_string(path).unwrap_or_else(|why| panic!("couldn't read {}: {}",path,why));
+            key_script = headless::parse_key_script(&script).unwrap_or_else(|error| panic!("{}",error));
+        } else if arg == "-output"{
+            output_path = Some(options.next().expect("Error: -output needs a file to write the final state to"));
+        } else if arg == "-screenshot"{
+            screenshot_path = Some(options.next().expect("Error: -screenshot needs a file to write the framebuffer to"));
+        } else if arg == "-quirk"{
+            quirk_overrides.push(options.next().expect("Error: -quirk needs a quirk in the form name=on or name=off"));
         } else {
             panic!("Option not recognized: {}",arg)
         }
 
     }
+
+    //Overrides are applied after the profile so they work in any order on the command line
+    //Colours given on the command line replace the theme's colours whichever order they are given in
+    for (planes, color) in color_overrides {
+        match palette::parse_color(color) {
+            Some(color) => palette.set(planes, color),
+            None => panic!("Colour not recognized: {} (expected RRGGBB in hex or default)",color)
+        }
+    }
+
+    for quirk in quirk_overrides {
+        let recognized = match quirk.split_once('=') {
+            Some((flag, "on")) => quirks.set(flag, true),
+            Some((flag, "off")) => quirks.set(flag, false),
+            _ => false
+        };
+        if !recognized {
+            panic!("Quirk not recognized: {} (expected name=on or name=off with a name from {})",quirk,quirks::FLAGS.join(", "));
+        }
+    }
     
     //Initialize the components of the emulator
     let rom = io::load_rom(&args[1]);
-    let mut emu = chip8::init(rom);
-    let mut engine = io::init();
+    let rom_hash = savestate::hash(&rom);
+    let mut emu = chip8::init(rom.clone(), quirks);
+
+    //A save state given on the command line is loaded before the terminal is taken over so errors can be printed
+    if let Some(load_slot) = load_slot {
+        if let Err(error) = load_state(&mut emu, &args[1], load_slot, rom_hash) {
+            panic!("Couldn't load save state from slot {}: {}",load_slot,error);
+        }
+    }
+
+    let mut trace = trace_path.map(|path| match Trace::create(path, trace_format, trace_range, trace_patterns) {
+        Ok(trace) => trace,
+        Err(error) => panic!("Couldn't create trace file {}: {}",path,error)
+    });
+
+    let cycles_per_frame = cpu_hz/FPS as f64;
+
+    //Headless runs need no terminal or audio device, so they can run in CI
+    if headless {
+        if gdb_port.is_some() {
+            panic!("Error: -gdb can not be used with -headless");
+        }
+        if frame_limit.is_none() && cycle_limit.is_none() {
+            frame_limit = Some(DEFAULT_HEADLESS_FRAMES);
+        }
+        let run = headless::run(&mut emu, frame_limit, cycle_limit, cycles_per_frame, &key_script, &mut trace);
+        let report = headless::report(&emu, &run);
+        match output_path {
+            Some(path) => fs::write(path, report).unwrap_or_else(|why| panic!("couldn't write to {}: {}",path,why)),
+            None => print!("{}",report)
+        }
+        if let Some(path) = screenshot_path {
+            let pbm = headless::framebuffer_pbm(&emu.gfx, emu.width(), emu.height());
+            fs::write(path, pbm).unwrap_or_else(|why| panic!("couldn't write to {}: {}",path,why));
+        }
+        if let Some(trace) = trace {
+            if let Err(error) = trace.finish() {
+                eprintln!("Couldn't write trace: {}",error);
+            }
+        }
+        //A halted CPU fails the run so scripts can detect it
+        if let StopReason::Halted(_) = run.stop {
+            std::process::exit(1);
+        }
+        return;
+    }
 
-    //Target graphics FPS of 60
-    let fps = 60;
-    let frame_time_ms = (1/fps)*1000 as u128;
+    //With GDB the program waits in step mode until the debugger continues it
+    let mut gdb = gdb_port.map(|port| match GdbStub::listen(port) {
+        Ok(gdb) => gdb,
+        Err(error) => panic!("Couldn't listen for GDB on port {}: {}",port,error)
+    });
+    if gdb.is_some() {
+        step = true;
+    }
 
-    //Target cpu speed of 500 Hz
-    let cpu_speed = 500;
-    let cpu_time_ms = (1/cpu_speed)*1000 as u128;
+    let mut engine = io::init(render_mode, palette);
+    let mut display_filter = DisplayFilter::new(filter_mode);
 
-    //Set to true when sound beep is needed
-    let mut beep = false;
+    let frame_time = Duration::from_secs(1)/FPS;
 
     //Set to true when debugging info needs to be redrawn on screen
     let mut debug_redraw = true;
@@ -64,54 +271,312 @@ pub fn emulator_loop() {
     //Used when in step mode to step to the next CPU instruction
     let mut next_step = false;
 
+    //Pausing stops the emulation without entering step mode, speed is changed by turbo and slow motion
+    let mut paused = false;
+    let mut speed = 1.0;
+
+    /*Emulated frames and CPU cycles that are owed, the fractional parts carry over so that slow motion and CPU
+    speeds that are not a multiple of 60 Hz run at the right average rate*/
+    let mut frames_owed = 0.0;
+    let mut cycles_owed = 0.0;
+
+    let mut next_frame = time::Instant::now();
+
+    //Snapshots for rewinding frames, and instructions in step mode
+    let mut rewind = Rewind::new();
+
+    //The breakpoint or watchpoint that last stopped the emulation, cleared when it resumes
+    let mut hit = None;
+
+    //Result of the last save state or rewind hotkey, shown on the status line
+    let mut message = match gdb_port {
+        Some(port) => format!("Waiting for GDB on port {}",port),
+        None => "".to_string()
+    };
+
     let mut exit = false;
 
     while !exit {
 
-        let start_time_fps = time::Instant::now();
-        let mut draw = false;
-        
-        //Keep cycling if there is still time until the screen needs to be drawn to the target FPS
-        while (frame_time_ms.saturating_sub(start_time_fps.elapsed().as_millis()) > 0  || !draw) && !exit {
-            let start_time_cpu = time::Instant::now();
-
-            //If in step mode then wait for the next CPU instruction to be requested to emulate another cpu cycle
-            //Also if a draw is requested wait to emulate the next CPU cycle until the screen is drawn
-            if (!step || next_step) && !draw {
-                emu.cycle(debug, &mut draw, &mut beep);
+        if step {
+            //In step mode wait for the next CPU instruction to be requested to emulate another cpu cycle
+            if next_step {
+                rewind.push(&emu, SnapshotKind::Instruction);
+                if let Some(StepOutcome::Exit) = emulate_cycle(&mut emu, &mut trace, &mut debug, &mut step) {
+                    exit = true;
+                }
+                debug_redraw = true;
             }
+            frames_owed = 0.0;
+        } else if !paused {
+            frames_owed += speed;
+            while frames_owed >= 1.0 && !step && !exit {
+                frames_owed -= 1.0;
+                rewind.push(&emu, SnapshotKind::Frame);
 
-            if debug_redraw{
-                engine.info_draw(emu.to_owned().debug_info,debug,step);
+                //Emulate the instructions for this frame, breakpoints and watchpoints stop before the instruction runs
+                let result = frontend::emulate_frame(&mut emu, &mut cycles_owed, cycles_per_frame, |emu| {
+                    if let Some(new_hit) = debugger.check(emu) {
+                        hit = Some(new_hit);
+                        step = true;
+                        debug = true;
+                        emu.refresh_debug_info();
+                        debug_redraw = true;
+                        return None;
+                    }
+                    if let Some(trace) = trace.as_mut() {
+                        trace.record(emu);
+                    }
+                    Some(emu.cycle(debug))
+                });
+                display_filter.apply(&emu.gfx);
+                match result {
+                    Ok(StepOutcome::Exit) => exit = true,
+                    Ok(_) => {}
+                    //The CPU halted, show the instruction that failed in step mode
+                    Err(_) => {
+                        step = true;
+                        debug = true;
+                        debug_redraw = true;
+                    }
+                }
             }
+        }
 
-            engine.sound(&mut beep);
+        if let Some(gdb) = gdb.as_mut() {
+            match gdb.poll(&mut emu, &mut debugger) {
+                GdbAction::Continue => {
+                    step = false;
+                    hit = None;
+                }
+                GdbAction::Stop => step = true,
+                GdbAction::Kill => exit = true,
+                GdbAction::None => {}
+            }
+            if step {
+                gdb.stopped(&emu);
+            }
+        }
 
-            let key_actions = engine.input(&mut emu.keypad);
+        if debug_redraw{
+            engine.info_draw(&emu.debug_info,debug,step,emu.halted(),hit.as_ref(),&status_line(paused, speed, &message));
+        }
 
-            exit = key_actions.exit;
-            next_step = key_actions.next_step;
-            if key_actions.step {
-                step = !step;
-                debug_redraw = true;
+        engine.sound(emu.sound_playing() && !paused && !step, emu.audio_pattern(), emu.audio_pitch());
+
+        let key_actions = engine.input(&mut emu.keypad);
+
+        exit |= key_actions.exit;
+        next_step = key_actions.next_step;
+        if key_actions.step {
+            step = !step;
+            if !step {
+                debugger.resume();
+                hit = None;
             }
-            if key_actions.debug {
-                debug = !debug;
-                debug_redraw = true;
+            debug_redraw = true;
+        }
+        if key_actions.debug {
+            debug = !debug;
+            debug_redraw = true;
+        }
+        if key_actions.mem_dump {
+            io::write_mem_dump_file(emu.memory());
+        }
+        if key_actions.pause {
+            paused = !paused;
+        }
+        if key_actions.turbo {
+            speed = if speed == TURBO_SPEED { 1.0 } else { TURBO_SPEED };
+        }
+        if key_actions.slow_motion {
+            speed = if speed == SLOW_MOTION_SPEED { 1.0 } else { SLOW_MOTION_SPEED };
+        }
+        if key_actions.rewind {
+            message = match rewind.pop() {
+                Some((previous, kind)) => {
+                    //The keypad is kept since it reflects the keys currently held rather than the past
+                    let keypad = emu.keypad;
+                    emu = previous;
+                    emu.keypad = keypad;
+                    match kind {
+                        SnapshotKind::Frame => "Rewound one frame, press Space to resume".to_string(),
+                        SnapshotKind::Instruction => "Stepped back one instruction".to_string()
+                    }
+                }
+                None => "Nothing left to rewind".to_string()
+            };
+            //Outside of step mode the emulation is paused so each press rewinds one more frame
+            if !step {
+                paused = true;
             }
-            if key_actions.mem_dump {
-                io::write_mem_dump_file(emu.to_owned().mem_dump());
+            debug_redraw = true;
+        }
+        if let Some(command) = key_actions.command {
+            let mut output = vec![format!("> {}",command)];
+            match Command::parse(&command) {
+                Ok(Command::Break(breakpoint)) => {
+                    output.push(format!("Added {}",breakpoint));
+                    debugger.add_breakpoint(breakpoint);
+                }
+                Ok(Command::Watch(watchpoint)) => {
+                    output.push(format!("Added {}",watchpoint));
+                    debugger.add_watchpoint(watchpoint);
+                }
+                Ok(Command::Set { target, value }) => {
+                    match target {
+                        Target::V(reg) => emu.set_register(reg as usize, value as u8),
+                        Target::I => emu.set_i(value),
+                        Target::Pc => emu.set_pc(value)
+                    }
+                    emu.refresh_debug_info();
+                }
+                Ok(Command::Poke { address, value }) => {
+                    emu.poke(address as usize, value);
+                    emu.refresh_debug_info();
+                }
+                Ok(Command::Mem { address, len }) => output.extend(console::hex_dump(emu.memory(), address, len)),
+                Ok(Command::Continue) => {
+                    step = false;
+                    paused = false;
+                    debugger.resume();
+                    hit = None;
+                }
+                Ok(Command::Step(count)) => {
+                    /*The instruction stopped at runs even if it is a breakpoint, the following instructions stop on
+                    breakpoints and watchpoints like the free running loop*/
+                    step = true;
+                    hit = None;
+                    if count > 0 {
+                        debugger.resume();
+                    }
+                    for _ in 0..count {
+                        if let Some(new_hit) = debugger.check(&emu) {
+                            output.push(new_hit.to_string());
+                            hit = Some(new_hit);
+                            debug = true;
+                            emu.refresh_debug_info();
+                            break;
+                        }
+                        rewind.push(&emu, SnapshotKind::Instruction);
+                        match emulate_cycle(&mut emu, &mut trace, &mut debug, &mut step) {
+                            Some(StepOutcome::Exit) => {
+                                exit = true;
+                                break;
+                            }
+                            None => break,
+                            _ => {}
+                        }
+                    }
+                }
+                Ok(Command::Reset) => {
+                    emu = chip8::init(rom.clone(), quirks);
+                    hit = None;
+                }
+                Ok(Command::Help) => output.extend(console::HELP.iter().map(|line| line.to_string())),
+                Err(error) => output.push(error)
             }
+            engine.console_print(output);
+            debug_redraw = true;
+        }
+        if key_actions.memory_view_i {
+            engine.memory_view_to(emu.i());
+        }
+        if key_actions.next_slot {
+            slot = (slot + 1) % savestate::SLOTS;
+            message = format!("Save state slot {}",slot);
+        }
+        if key_actions.previous_slot {
+            slot = (slot + savestate::SLOTS - 1) % savestate::SLOTS;
+            message = format!("Save state slot {}",slot);
+        }
+        if key_actions.save_state {
+            message = match io::write_save_state_file(&args[1], slot, &savestate::encode(&emu, rom_hash)) {
+                Ok(()) => format!("Saved state to slot {}",slot),
+                Err(error) => format!("Couldn't save state to slot {}: {}",slot,error)
+            };
+        }
+        if key_actions.load_state {
+            message = match load_state(&mut emu, &args[1], slot, rom_hash) {
+                Ok(()) => format!("Loaded state from slot {}",slot),
+                Err(error) => format!("Couldn't load state from slot {}: {}",slot,error)
+            };
+        }
 
-            let cpu_time_remaining = cpu_time_ms.saturating_sub(start_time_cpu.elapsed().as_micros());
-            if cpu_time_remaining > 0 {
-                sleep(Duration::from_micros(cpu_time_remaining as u64));
-            }
+        engine.draw(display_filter.shown(&emu.gfx), emu.width(), emu.height());
+        engine.panes_draw(emu.memory(), emu.pc(), emu.i(), debug);
+
+        /*Each frame is scheduled from the previous frame's deadline rather than from when it finished, so time lost
+        oversleeping is made up in the next frame instead of accumulating*/
+        next_frame += frame_time;
+        let now = time::Instant::now();
+        if next_frame > now {
+            sleep(next_frame - now);
+        } else if now - next_frame > frame_time*MAX_FRAMES_BEHIND {
+            next_frame = now;
         }
-        engine.draw(emu.to_owned().gfx);
     }
 
     engine.deinit();
+
+    if let Some(gdb) = gdb.as_mut() {
+        gdb.exited();
+    }
+
+    if let Some(trace) = trace {
+        if let Err(error) = trace.finish() {
+            eprintln!("Couldn't write trace: {}",error);
+        }
+    }
+
+    if let Some(save_slot) = save_slot_on_exit {
+        if let Err(error) = io::write_save_state_file(&args[1], save_slot, &savestate::encode(&emu, rom_hash)) {
+            panic!("Couldn't save state to slot {}: {}",save_slot,error);
+        }
+    }
+}
+
+//Emulates one CPU cycle, returns None if the CPU halted after pausing with the debug information shown
+fn emulate_cycle(emu: &mut Chip8, trace: &mut Option<Trace>, debug: &mut bool, step: &mut bool) -> Option<StepOutcome> {
+    if let Some(trace) = trace {
+        trace.record(emu);
+    }
+    match emu.cycle(*debug) {
+        Ok(outcome) => Some(outcome),
+        Err(_) => {
+            *step = true;
+            *debug = true;
+            None
+        }
+    }
+}
+
+//Describes the emulation speed for the status line, followed by the last save state or rewind message
+fn status_line(paused: bool, speed: f64, message: &str) -> String {
+    let speed_status = if paused {
+        "Paused, press Space to resume".to_string()
+    } else if speed != 1.0 {
+        format!("Running at {}x speed", speed)
+    } else {
+        "".to_string()
+    };
+    if speed_status.is_empty() || message.is_empty() {
+        speed_status + message
+    } else {
+        format!("{}. {}", speed_status, message)
+    }
+}
+
+fn parse_slot(slot: &str) -> u8 {
+    match slot.parse::<u8>() {
+        Ok(slot) if slot < savestate::SLOTS => slot,
+        _ => panic!("Save state slot not recognized: {} (expected 0 to {})",slot,savestate::SLOTS-1)
+    }
+}
+
+fn load_state(emu: &mut Chip8, rom: &str, slot: u8, rom_hash: u64) -> Result<(), savestate::SaveStateError> {
+    let state = io::read_save_state_file(rom, slot)?;
+    savestate::decode(emu, &state, rom_hash)
 }
 
 
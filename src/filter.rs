@@ -0,0 +1,112 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*Programs erase and redraw their sprites with XOR, so a sprite that moves is missing from some frames and flickers.
+A display filter sits between the framebuffer and the front-end and keeps pixels on screen for longer*/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FilterMode {
+    //Shows the framebuffer unchanged
+    None,
+    //Shows a pixel when it is lit in either of the last two frames
+    Blend,
+    //Pixels fade out over a number of frames after they go off, like the phosphor of a CRT
+    Decay
+}
+
+pub const FILTER_MODES: [&str; 3] = ["none", "blend", "decay"];
+
+//Number of frames a pixel takes to fade out with the decay filter
+pub const DECAY_LEVELS: u8 = 3;
+
+impl FilterMode {
+    pub fn parse(name: &str) -> Option<FilterMode> {
+        match name {
+            "none" => Some(FilterMode::None),
+            "blend" => Some(FilterMode::Blend),
+            "decay" => Some(FilterMode::Decay),
+            _ => None
+        }
+    }
+}
+
+/*The filtered pixels have the planes in the lower two bits, as in the framebuffer, and how far the pixel has faded in
+the bits above them, from 0 for a lit pixel to DECAY_LEVELS*/
+pub struct DisplayFilter {
+    mode: FilterMode,
+    previous: Vec<u8>,
+    filtered: Vec<u8>,
+    //The framebuffer that was last filtered
+    source: Vec<u8>
+}
+
+impl DisplayFilter {
+    pub fn new(mode: FilterMode) -> DisplayFilter {
+        DisplayFilter {
+            mode,
+            previous: Vec::new(),
+            filtered: Vec::new(),
+            source: Vec::new()
+        }
+    }
+
+    /*Filters a frame, called once for each emulated frame since the filters count frames. The filter starts over when
+    the resolution changes*/
+    pub fn apply(&mut self, gfx: &[u8]) -> &[u8] {
+        if self.filtered.len() != gfx.len() {
+            self.previous = gfx.to_vec();
+            self.filtered = gfx.to_vec();
+            self.source = gfx.to_vec();
+        }
+        self.source.copy_from_slice(gfx);
+        match self.mode {
+            FilterMode::None => self.filtered.copy_from_slice(gfx),
+            FilterMode::Blend => {
+                for ((filtered, previous), &pixel) in self.filtered.iter_mut().zip(self.previous.iter_mut()).zip(gfx) {
+                    *filtered = pixel | *previous;
+                    *previous = pixel;
+                }
+            }
+            FilterMode::Decay => {
+                for (filtered, &pixel) in self.filtered.iter_mut().zip(gfx) {
+                    *filtered = if pixel != 0 {
+                        pixel
+                    } else if *filtered != 0 && fade(*filtered) < DECAY_LEVELS {
+                        planes(*filtered) | (fade(*filtered) + 1) << 2
+                    } else {
+                        0
+                    };
+                }
+            }
+        }
+        &self.filtered
+    }
+
+    /*The last filtered frame while the framebuffer is unchanged since it was filtered, so pausing or running slower
+    than the frame rate keeps showing it. Changes made between frames, such as single steps or loading a state, are
+    shown unfiltered*/
+    pub fn shown<'a>(&'a self, gfx: &'a [u8]) -> &'a [u8] {
+        if self.source == gfx {
+            &self.filtered
+        } else {
+            gfx
+        }
+    }
+}
+
+pub fn planes(pixel: u8) -> u8 {
+    pixel & 0x3
+}
+
+pub fn fade(pixel: u8) -> u8 {
+    pixel >> 2
+}
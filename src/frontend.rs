@@ -0,0 +1,72 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::chip8::{Chip8, CpuError, StepOutcome};
+
+//Shows the framebuffer, where each pixel is a bitmask of the XO-CHIP planes it is lit on
+pub trait Display {
+    fn draw(&mut self, gfx: &[u8], width: usize, height: usize);
+}
+
+//Sets each key of the keypad to 1 while it is held and 0 otherwise
+pub trait Input {
+    fn update_keypad(&mut self, keypad: &mut [u8; 16]);
+}
+
+//Plays the 1-bit audio pattern at the XO-CHIP pitch while the sound timer is running
+pub trait Audio {
+    fn sound(&mut self, playing: bool, pattern: [u8; 16], pitch: u8);
+}
+
+/*Emulates one 60 Hz frame for a front-end: reads the keypad, runs the instructions owed for the frame and ticks the
+timers, then presents the display and sound*/
+pub fn run_frame<F: Display + Input + Audio>(emu: &mut Chip8, frontend: &mut F, cycles_owed: &mut f64, cycles_per_frame: f64) -> Result<StepOutcome, CpuError> {
+    frontend.update_keypad(&mut emu.keypad);
+
+    let outcome = emulate_frame(emu, cycles_owed, cycles_per_frame, |emu| Some(emu.cycle(false)))?;
+    if outcome == StepOutcome::Exit {
+        return Ok(outcome);
+    }
+
+    frontend.draw(&emu.gfx, emu.width(), emu.height());
+    frontend.sound(emu.sound_playing(), emu.audio_pattern(), emu.audio_pitch());
+    Ok(outcome)
+}
+
+/*Runs the instructions owed for one 60 Hz frame, then ticks the timers. The fractional part of the cycles owed carries
+over to the next frame, and with the display wait quirk a draw ends the frame's instructions. Each instruction is run
+by the cycle closure, which can return None instead to stop before the instruction, leaving the frame unfinished and
+the timers unticked. Returns Draw if any instruction drew, or Exit as soon as the program exits*/
+pub fn emulate_frame<C>(emu: &mut Chip8, cycles_owed: &mut f64, cycles_per_frame: f64, mut cycle: C) -> Result<StepOutcome, CpuError>
+where C: FnMut(&mut Chip8) -> Option<Result<StepOutcome, CpuError>> {
+    let mut outcome = StepOutcome::Continue;
+    *cycles_owed += cycles_per_frame;
+    while *cycles_owed >= 1.0 {
+        let result = match cycle(emu) {
+            Some(result) => result,
+            None => return Ok(outcome)
+        };
+        *cycles_owed -= 1.0;
+        match result? {
+            StepOutcome::Draw => {
+                outcome = StepOutcome::Draw;
+                if emu.quirks.display_wait {
+                    *cycles_owed = cycles_owed.fract();
+                }
+            }
+            StepOutcome::Exit => return Ok(StepOutcome::Exit),
+            StepOutcome::Continue => {}
+        }
+    }
+    emu.tick_timers();
+    Ok(outcome)
+}
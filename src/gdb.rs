@@ -0,0 +1,325 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::sleep;
+use std::time::Duration;
+
+use crate::chip8::{Chip8, StepOutcome, MEMORY_SIZE};
+use crate::debugger::{Breakpoint, Debugger};
+
+//Register numbers used by GDB: V0-VF, then I, PC and SP
+const REGISTER_I: usize = 16;
+const REGISTER_PC: usize = 17;
+const REGISTER_SP: usize = 18;
+
+//Describes the registers so GDB does not need to know about CHIP-8
+const TARGET_XML: &str = concat!(
+    r#"<?xml version="1.0"?><!DOCTYPE target SYSTEM "gdb-target.dtd"><target><feature name="org.chip8.cpu">"#,
+    r#"<reg name="v0" bitsize="8"/><reg name="v1" bitsize="8"/><reg name="v2" bitsize="8"/><reg name="v3" bitsize="8"/>"#,
+    r#"<reg name="v4" bitsize="8"/><reg name="v5" bitsize="8"/><reg name="v6" bitsize="8"/><reg name="v7" bitsize="8"/>"#,
+    r#"<reg name="v8" bitsize="8"/><reg name="v9" bitsize="8"/><reg name="va" bitsize="8"/><reg name="vb" bitsize="8"/>"#,
+    r#"<reg name="vc" bitsize="8"/><reg name="vd" bitsize="8"/><reg name="ve" bitsize="8"/><reg name="vf" bitsize="8"/>"#,
+    r#"<reg name="i" bitsize="16" type="data_ptr"/><reg name="pc" bitsize="16" type="code_ptr"/>"#,
+    r#"<reg name="sp" bitsize="16"/></feature></target>"#
+);
+
+//Stop reply signals, a trap for breakpoints and steps, an interrupt for Ctrl-C and an illegal instruction for halts
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+
+//What the emulator has to do after the packets received by the stub were handled
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GdbAction {
+    None,
+    Continue,
+    Stop,
+    Kill
+}
+
+/*GDB remote serial protocol stub on localhost. It is polled once per frame without blocking, so the emulator keeps
+drawing the screen while a debugger is attached*/
+pub struct GdbStub {
+    listener: TcpListener,
+    stream: Option<TcpStream>,
+    buffer: Vec<u8>,
+    //Whether GDB is waiting for a stop reply after continuing
+    running: bool
+}
+
+impl GdbStub {
+    pub fn listen(port: u16) -> io::Result<GdbStub> {
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        listener.set_nonblocking(true)?;
+        Ok(GdbStub {
+            listener,
+            stream: None,
+            buffer: Vec::new(),
+            running: false
+        })
+    }
+
+    /*Accepts a connection and handles the packets received since the last poll. Stepping is done here, while
+    continuing is left to the emulator loop, which reports back through stopped when the CPU stops*/
+    pub fn poll(&mut self, emu: &mut Chip8, debugger: &mut Debugger) -> GdbAction {
+        if self.stream.is_none() {
+            match self.listener.accept() {
+                Ok((stream, _)) if stream.set_nonblocking(true).is_ok() => {
+                    self.stream = Some(stream);
+                    self.buffer.clear();
+                    self.running = false;
+                    //The program is stopped while the debugger attaches
+                    return GdbAction::Stop;
+                }
+                _ => return GdbAction::None
+            }
+        }
+
+        let mut received = [0; 4096];
+        loop {
+            let read = match self.stream.as_mut().unwrap().read(&mut received) {
+                Ok(0) => {
+                    //GDB disconnected, so the program runs on by itself
+                    self.stream = None;
+                    return GdbAction::Continue;
+                }
+                Ok(read) => read,
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(_) => {
+                    self.stream = None;
+                    return GdbAction::Continue;
+                }
+            };
+            self.buffer.extend_from_slice(&received[..read]);
+        }
+
+        let mut action = GdbAction::None;
+        while let Some(packet) = self.next_packet(&mut action) {
+            let packet_action = self.handle(&packet, emu, debugger);
+            if packet_action != GdbAction::None {
+                action = packet_action;
+            }
+        }
+        action
+    }
+
+    //Sends the stop reply GDB is waiting for once the emulator stops at a breakpoint or halts
+    pub fn stopped(&mut self, emu: &Chip8) {
+        if self.running {
+            self.running = false;
+            let signal = if emu.halted().is_some() { SIGILL } else { SIGTRAP };
+            self.send(&format!("S{:02x}", signal));
+        }
+    }
+
+    //Tells GDB the program exited with 00FD
+    pub fn exited(&mut self) {
+        self.send("W00");
+    }
+
+    //Takes the next complete packet out of the buffer, acknowledging it. A Ctrl-C byte between packets stops the CPU
+    fn next_packet(&mut self, action: &mut GdbAction) -> Option<String> {
+        loop {
+            match self.buffer.first()? {
+                b'$' => break,
+                0x03 => {
+                    *action = GdbAction::Stop;
+                    if self.running {
+                        self.running = false;
+                        self.send(&format!("S{:02x}", SIGINT));
+                    }
+                }
+                _ => {}
+            }
+            self.buffer.remove(0);
+        }
+        let end = self.buffer.iter().position(|&byte| byte == b'#')?;
+        if self.buffer.len() < end + 3 {
+            return None;
+        }
+        let packet: Vec<u8> = self.buffer.drain(..end + 3).collect();
+        self.write_raw(b"+");
+        Some(String::from_utf8_lossy(&packet[1..end]).into_owned())
+    }
+
+    fn handle(&mut self, packet: &str, emu: &mut Chip8, debugger: &mut Debugger) -> GdbAction {
+        //The command is the first character, which is not a single byte if the packet was not valid UTF-8
+        let (command, args) = packet.split_at(packet.chars().next().map_or(0, char::len_utf8));
+        let reply = match command {
+            "?" => format!("S{:02x}", SIGTRAP),
+            "g" => (0..=REGISTER_SP).map(|reg| read_register(emu, reg)).collect(),
+            "G" => {
+                let mut args = args;
+                for reg in 0..=REGISTER_SP {
+                    let len = if reg < REGISTER_I { 2 } else { 4 };
+                    let value = match args.get(..len) {
+                        Some(value) => value,
+                        None => break
+                    };
+                    write_register(emu, reg, value);
+                    args = &args[len..];
+                }
+                emu.refresh_debug_info();
+                "OK".to_string()
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(reg) if reg <= REGISTER_SP => read_register(emu, reg),
+                _ => "E01".to_string()
+            },
+            "P" => match args.split_once('=').map(|(reg, value)| (usize::from_str_radix(reg, 16), value)) {
+                Some((Ok(reg), value)) if reg <= REGISTER_SP && write_register(emu, reg, value) => {
+                    emu.refresh_debug_info();
+                    "OK".to_string()
+                }
+                _ => "E01".to_string()
+            },
+            "m" => match parse_range(args) {
+                Some((address, len)) => emu.memory()[address..address+len].iter().map(|byte| format!("{:02x}", byte)).collect(),
+                None => "E01".to_string()
+            },
+            "M" => match args.split_once(':').and_then(|(range, data)| Some((parse_range(range)?, decode_hex(data)?))) {
+                Some(((address, len), data)) if data.len() == len => {
+                    for (offset, byte) in data.into_iter().enumerate() {
+                        emu.poke(address + offset, byte);
+                    }
+                    emu.refresh_debug_info();
+                    "OK".to_string()
+                }
+                _ => "E01".to_string()
+            },
+            "s" => {
+                //Single steps are run straight away and answered with a stop reply
+                let signal = match emu.cycle(true) {
+                    Ok(StepOutcome::Exit) => {
+                        self.exited();
+                        return GdbAction::Kill;
+                    }
+                    Ok(_) => SIGTRAP,
+                    Err(_) => SIGILL
+                };
+                format!("S{:02x}", signal)
+            }
+            "c" => {
+                //The stop reply is sent later, when the emulator stops
+                self.running = true;
+                debugger.resume();
+                return GdbAction::Continue;
+            }
+            "Z" | "z" => match args.split(',').collect::<Vec<&str>>().as_slice() {
+                //Software and hardware breakpoints are both treated as PC breakpoints
+                ["0", address, _] | ["1", address, _] => match u16::from_str_radix(address, 16) {
+                    Ok(address) if command == "Z" => {
+                        debugger.add_breakpoint(Breakpoint::Pc(address));
+                        "OK".to_string()
+                    }
+                    Ok(address) => {
+                        debugger.remove_breakpoint(&Breakpoint::Pc(address));
+                        "OK".to_string()
+                    }
+                    Err(_) => "E01".to_string()
+                },
+                _ => "".to_string()
+            },
+            "H" | "T" => "OK".to_string(),
+            "D" => {
+                self.send("OK");
+                self.stream = None;
+                return GdbAction::Continue;
+            }
+            "k" => {
+                self.stream = None;
+                return GdbAction::Kill;
+            }
+            "q" if args.starts_with("Supported") => "PacketSize=1000;qXfer:features:read+".to_string(),
+            "q" if args == "Attached" => "1".to_string(),
+            "q" if args.starts_with("Xfer:features:read:target.xml:") => {
+                let range = &args["Xfer:features:read:target.xml:".len()..];
+                match parse_range(range) {
+                    Some((offset, len)) if offset < TARGET_XML.len() => {
+                        let end = (offset + len).min(TARGET_XML.len());
+                        let more = if end < TARGET_XML.len() { "m" } else { "l" };
+                        format!("{}{}", more, &TARGET_XML[offset..end])
+                    }
+                    Some(_) => "l".to_string(),
+                    None => "E01".to_string()
+                }
+            }
+            //An empty reply tells GDB the packet is not supported
+            _ => "".to_string()
+        };
+        self.send(&reply);
+        GdbAction::None
+    }
+
+    fn send(&mut self, data: &str) {
+        let checksum = data.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        self.write_raw(format!("${}#{:02x}", data, checksum).as_bytes());
+    }
+
+    //Writes to the non-blocking stream, waiting while the socket buffer is full
+    fn write_raw(&mut self, mut data: &[u8]) {
+        while let Some(stream) = self.stream.as_mut() {
+            if data.is_empty() {
+                return;
+            }
+            match stream.write(data) {
+                Ok(written) => data = &data[written..],
+                Err(error) if error.kind() == ErrorKind::WouldBlock => sleep(Duration::from_millis(1)),
+                Err(_) => self.stream = None
+            }
+        }
+    }
+}
+
+//Registers are sent as little endian hex
+fn read_register(emu: &Chip8, reg: usize) -> String {
+    let value = match reg {
+        REGISTER_I => emu.i(),
+        REGISTER_PC => emu.pc(),
+        REGISTER_SP => emu.sp(),
+        _ => return format!("{:02x}", emu.register(reg))
+    };
+    value.to_le_bytes().iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn write_register(emu: &mut Chip8, reg: usize, hex: &str) -> bool {
+    let bytes = match decode_hex(hex) {
+        Some(bytes) => bytes,
+        None => return false
+    };
+    match (reg, bytes.as_slice()) {
+        (REGISTER_I, [low, high]) => emu.set_i(u16::from_le_bytes([*low, *high])),
+        (REGISTER_PC, [low, high]) => emu.set_pc(u16::from_le_bytes([*low, *high])),
+        (REGISTER_SP, [low, high]) => emu.set_sp(u16::from_le_bytes([*low, *high])),
+        (reg, [value]) if reg < REGISTER_I => emu.set_register(reg, *value),
+        _ => return false
+    }
+    true
+}
+
+//Parses an address,length pair, which must lie within memory
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let (address, len) = range.split_once(',')?;
+    let address = usize::from_str_radix(address, 16).ok()?;
+    let len = usize::from_str_radix(len, 16).ok()?;
+    address.checked_add(len).filter(|end| *end <= MEMORY_SIZE)?;
+    Some((address, len))
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len()).step_by(2).map(|index| u8::from_str_radix(hex.get(index..index+2)?, 16).ok()).collect()
+}
@@ -0,0 +1,158 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crate::chip8::{Chip8, CpuError, StepOutcome};
+use crate::frontend;
+use crate::savestate;
+use crate::trace::Trace;
+
+//Characters used for each combination of the XO-CHIP display planes in ASCII framebuffers
+const PLANE_CHARS: [char; 4] = ['.', '#', '+', '@'];
+
+//A key pressed or released by the key script at the start of a frame
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ScriptedKey {
+    pub frame: u64,
+    pub key: usize,
+    pub pressed: bool
+}
+
+/*Parses a key script, one event per line in the form "<frame> press <key>" or "<frame> release <key>" with the key as
+a hex digit. Lines starting with # are comments*/
+pub fn parse_key_script(script: &str) -> Result<Vec<ScriptedKey>, String> {
+    let mut keys = Vec::new();
+    for (line_num, line) in script.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        let event = match parts.as_slice() {
+            [frame, action, key] => {
+                let frame = frame.parse::<u64>().ok();
+                let key = u8::from_str_radix(key, 16).ok().filter(|&key| key < 16);
+                let pressed = match *action {
+                    "press" => Some(true),
+                    "release" => Some(false),
+                    _ => None
+                };
+                match (frame, key, pressed) {
+                    (Some(frame), Some(key), Some(pressed)) => Some(ScriptedKey { frame, key: key as usize, pressed }),
+                    _ => None
+                }
+            }
+            _ => None
+        };
+        match event {
+            Some(event) => keys.push(event),
+            None => return Err(format!("Key script line {} not recognized: {} (expected <frame> press|release <key>)", line_num + 1, line))
+        }
+    }
+    keys.sort_by_key(|event| event.frame);
+    Ok(keys)
+}
+
+//Why the headless run ended
+#[derive(Clone, PartialEq, Debug)]
+pub enum StopReason {
+    Finished,
+    Exited,
+    Halted(CpuError)
+}
+
+pub struct HeadlessRun {
+    pub frames: u64,
+    pub cycles: u64,
+    pub stop: StopReason
+}
+
+/*Runs the machine without a terminal or audio until it has run the given number of frames or cycles, exits or halts.
+Frames are emulated the same way as in the terminal, with the timers ticking once per frame*/
+pub fn run(emu: &mut Chip8, frame_limit: Option<u64>, cycle_limit: Option<u64>, cycles_per_frame: f64,
+           keys: &[ScriptedKey], trace: &mut Option<Trace>) -> HeadlessRun {
+    let mut run = HeadlessRun { frames: 0, cycles: 0, stop: StopReason::Finished };
+    let mut keys = keys.iter().peekable();
+    let mut cycles_owed = 0.0;
+
+    while frame_limit.is_none_or(|limit| run.frames < limit) {
+        while let Some(event) = keys.next_if(|event| event.frame <= run.frames) {
+            emu.keypad[event.key] = event.pressed as u8;
+        }
+
+        let mut limit_reached = false;
+        let result = frontend::emulate_frame(emu, &mut cycles_owed, cycles_per_frame, |emu| {
+            if cycle_limit.is_some_and(|limit| run.cycles >= limit) {
+                limit_reached = true;
+                return None;
+            }
+            if let Some(trace) = trace.as_mut() {
+                trace.record(emu);
+            }
+            run.cycles += 1;
+            Some(emu.cycle(false))
+        });
+        match result {
+            _ if limit_reached => return run,
+            Ok(StepOutcome::Exit) => {
+                run.stop = StopReason::Exited;
+                return run;
+            }
+            Ok(_) => {}
+            Err(error) => {
+                run.stop = StopReason::Halted(error);
+                return run;
+            }
+        }
+        run.frames += 1;
+    }
+    run
+}
+
+//Summary of the final state: how the run ended, the registers, a memory checksum and the framebuffer
+pub fn report(emu: &Chip8, run: &HeadlessRun) -> String {
+    let stop = match &run.stop {
+        StopReason::Finished => "finished".to_string(),
+        StopReason::Exited => "exited".to_string(),
+        StopReason::Halted(error) => format!("halted: {}", error)
+    };
+    let registers: Vec<String> = (0..16).map(|reg| format!("{:02X}", emu.register(reg))).collect();
+    let mut report = String::new();
+    report.push_str(&format!("Frames: {}\nCycles: {}\nStopped: {}\n", run.frames, run.cycles, stop));
+    report.push_str(&format!("PC: {:#06X}\nI: {:#06X}\nSP: {:#06X}\nDelay Timer: {:#04X}\nSound Timer: {:#04X}\n",
+        emu.pc(), emu.i(), emu.sp(), emu.delay_timer(), emu.sound_timer()));
+    report.push_str(&format!("V0-VF: {}\n", registers.join(" ")));
+    report.push_str(&format!("Memory Checksum: {:#018X}\n", savestate::hash(emu.memory())));
+    report.push_str(&format!("Framebuffer {}x{}:\n", emu.width(), emu.height()));
+    report.push_str(&framebuffer_ascii(&emu.gfx, emu.width(), emu.height()));
+    report
+}
+
+//The framebuffer as one line of text per row, see PLANE_CHARS
+pub fn framebuffer_ascii(gfx: &[u8], width: usize, height: usize) -> String {
+    let mut ascii = String::with_capacity((width + 1)*height);
+    for row in gfx[..width*height].chunks(width) {
+        ascii.extend(row.iter().map(|&pixel| PLANE_CHARS[(pixel & 0x3) as usize]));
+        ascii.push('\n');
+    }
+    ascii
+}
+
+//The framebuffer as a plain PBM image, where pixels lit on any plane are black
+pub fn framebuffer_pbm(gfx: &[u8], width: usize, height: usize) -> String {
+    let mut pbm = format!("P1\n{} {}\n", width, height);
+    for row in gfx[..width*height].chunks(width) {
+        let pixels: Vec<&str> = row.iter().map(|&pixel| if pixel != 0 { "1" } else { "0" }).collect();
+        pbm.push_str(&pixels.join(" "));
+        pbm.push('\n');
+    }
+    pbm
+}
@@ -0,0 +1,229 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fmt;
+
+//A decoded CHIP-8, SUPER-CHIP or XO-CHIP instruction. X and Y are register numbers, N, NN and NNN are immediates
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Instruction {
+    Clear,                          //00E0
+    Return,                         //00EE
+    ScrollDown { n: u8 },           //00CN
+    ScrollUp { n: u8 },             //00DN
+    ScrollRight,                    //00FB
+    ScrollLeft,                     //00FC
+    Exit,                           //00FD
+    LowRes,                         //00FE
+    HighRes,                        //00FF
+    Jump { address: u16 },          //1NNN
+    Call { address: u16 },          //2NNN
+    SkipEqualByte { x: u8, byte: u8 },    //3XNN
+    SkipNotEqualByte { x: u8, byte: u8 }, //4XNN
+    SkipEqual { x: u8, y: u8 },     //5XY0
+    StoreRange { x: u8, y: u8 },    //5XY2
+    LoadRange { x: u8, y: u8 },     //5XY3
+    SetByte { x: u8, byte: u8 },    //6XNN
+    AddByte { x: u8, byte: u8 },    //7XNN
+    Set { x: u8, y: u8 },           //8XY0
+    Or { x: u8, y: u8 },            //8XY1
+    And { x: u8, y: u8 },           //8XY2
+    Xor { x: u8, y: u8 },           //8XY3
+    Add { x: u8, y: u8 },           //8XY4
+    Sub { x: u8, y: u8 },           //8XY5
+    ShiftRight { x: u8, y: u8 },    //8XY6
+    SubReverse { x: u8, y: u8 },    //8XY7
+    ShiftLeft { x: u8, y: u8 },     //8XYE
+    SkipNotEqual { x: u8, y: u8 },  //9XY0
+    SetI { address: u16 },          //ANNN
+    JumpOffset { x: u8, address: u16 }, //BNNN, X is only used with the jumping quirk
+    Random { x: u8, byte: u8 },     //CXNN
+    Draw { x: u8, y: u8, n: u8 },   //DXYN
+    SkipKeyPressed { x: u8 },       //EX9E
+    SkipKeyNotPressed { x: u8 },    //EXA1
+    SetILong { address: u16 },      //F000 NNNN, the address is in the two bytes following the opcode
+    SelectPlanes { n: u8 },         //FN01
+    LoadAudio,                      //F002
+    GetDelay { x: u8 },             //FX07
+    WaitKey { x: u8 },              //FX0A
+    SetDelay { x: u8 },             //FX15
+    SetSound { x: u8 },             //FX18
+    AddI { x: u8 },                 //FX1E
+    Font { x: u8 },                 //FX29
+    BigFont { x: u8 },              //FX30
+    Bcd { x: u8 },                  //FX33
+    SetPitch { x: u8 },             //FX3A
+    Store { x: u8 },                //FX55
+    Load { x: u8 },                 //FX65
+    StoreFlags { x: u8 },           //FX75
+    LoadFlags { x: u8 },            //FX85
+    Unknown { opcode: u16 }
+}
+
+impl Instruction {
+    //Size of the instruction in bytes, including the address following F000
+    pub fn size(&self) -> u16 {
+        match self {
+            Instruction::SetILong { .. } => 4,
+            _ => 2
+        }
+    }
+}
+
+/*Decodes the instruction at the start of the bytes, returns it with its size in bytes, or None if the bytes end before
+the instruction does*/
+pub fn decode_at(bytes: &[u8]) -> Option<(Instruction, usize)> {
+    let word = |index: usize| -> Option<u16> { Some(((*bytes.get(index)? as u16) << 8) | *bytes.get(index+1)? as u16) };
+    let opcode = word(0)?;
+    let instruction = if opcode == 0xF000 {
+        Instruction::SetILong { address: word(2)? }
+    } else {
+        decode(opcode)
+    };
+    Some((instruction, instruction.size() as usize))
+}
+
+/*Decodes a single opcode. F000 is the first word of the four byte LD I, NNNN whose address is in the following word,
+so on its own it decodes as Unknown { opcode: 0xF000 }, use decode_at to decode it as SetILong*/
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & 0x0F00) >> 8) as u8;
+    let y = ((opcode & 0x00F0) >> 4) as u8;
+    let n = (opcode & 0x000F) as u8;
+    let byte = (opcode & 0x00FF) as u8;
+    let address = opcode & 0x0FFF;
+
+    match opcode & 0xF000 {
+        0x0000 => match opcode {
+            0x00E0 => Instruction::Clear,
+            0x00EE => Instruction::Return,
+            0x00FB => Instruction::ScrollRight,
+            0x00FC => Instruction::ScrollLeft,
+            0x00FD => Instruction::Exit,
+            0x00FE => Instruction::LowRes,
+            0x00FF => Instruction::HighRes,
+            _ if opcode & 0xFFF0 == 0x00C0 => Instruction::ScrollDown { n },
+            _ if opcode & 0xFFF0 == 0x00D0 => Instruction::ScrollUp { n },
+            _ => Instruction::Unknown { opcode }
+        },
+        0x1000 => Instruction::Jump { address },
+        0x2000 => Instruction::Call { address },
+        0x3000 => Instruction::SkipEqualByte { x, byte },
+        0x4000 => Instruction::SkipNotEqualByte { x, byte },
+        0x5000 => match n {
+            0x0 => Instruction::SkipEqual { x, y },
+            0x2 => Instruction::StoreRange { x, y },
+            0x3 => Instruction::LoadRange { x, y },
+            _ => Instruction::Unknown { opcode }
+        },
+        0x6000 => Instruction::SetByte { x, byte },
+        0x7000 => Instruction::AddByte { x, byte },
+        0x8000 => match n {
+            0x0 => Instruction::Set { x, y },
+            0x1 => Instruction::Or { x, y },
+            0x2 => Instruction::And { x, y },
+            0x3 => Instruction::Xor { x, y },
+            0x4 => Instruction::Add { x, y },
+            0x5 => Instruction::Sub { x, y },
+            0x6 => Instruction::ShiftRight { x, y },
+            0x7 => Instruction::SubReverse { x, y },
+            0xE => Instruction::ShiftLeft { x, y },
+            _ => Instruction::Unknown { opcode }
+        },
+        0x9000 if n == 0 => Instruction::SkipNotEqual { x, y },
+        0xA000 => Instruction::SetI { address },
+        0xB000 => Instruction::JumpOffset { x, address },
+        0xC000 => Instruction::Random { x, byte },
+        0xD000 => Instruction::Draw { x, y, n },
+        0xE000 => match byte {
+            0x9E => Instruction::SkipKeyPressed { x },
+            0xA1 => Instruction::SkipKeyNotPressed { x },
+            _ => Instruction::Unknown { opcode }
+        },
+        0xF000 => match byte {
+            0x01 => Instruction::SelectPlanes { n: x },
+            0x02 if x == 0 => Instruction::LoadAudio,
+            0x07 => Instruction::GetDelay { x },
+            0x0A => Instruction::WaitKey { x },
+            0x15 => Instruction::SetDelay { x },
+            0x18 => Instruction::SetSound { x },
+            0x1E => Instruction::AddI { x },
+            0x29 => Instruction::Font { x },
+            0x30 => Instruction::BigFont { x },
+            0x33 => Instruction::Bcd { x },
+            0x3A => Instruction::SetPitch { x },
+            0x55 => Instruction::Store { x },
+            0x65 => Instruction::Load { x },
+            0x75 => Instruction::StoreFlags { x },
+            0x85 => Instruction::LoadFlags { x },
+            _ => Instruction::Unknown { opcode }
+        },
+        _ => Instruction::Unknown { opcode }
+    }
+}
+
+//Mnemonics follow Cowgod's CHIP-8 reference, with the common names for the SUPER-CHIP and XO-CHIP extensions
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Clear => write!(f, "CLS"),
+            Instruction::Return => write!(f, "RET"),
+            Instruction::ScrollDown { n } => write!(f, "SCD {:#03X}", n),
+            Instruction::ScrollUp { n } => write!(f, "SCU {:#03X}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::LowRes => write!(f, "LOW"),
+            Instruction::HighRes => write!(f, "HIGH"),
+            Instruction::Jump { address } => write!(f, "JP {:#05X}", address),
+            Instruction::Call { address } => write!(f, "CALL {:#05X}", address),
+            Instruction::SkipEqualByte { x, byte } => write!(f, "SE V{:X}, {:#04X}", x, byte),
+            Instruction::SkipNotEqualByte { x, byte } => write!(f, "SNE V{:X}, {:#04X}", x, byte),
+            Instruction::SkipEqual { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::StoreRange { x, y } => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            Instruction::LoadRange { x, y } => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            Instruction::SetByte { x, byte } => write!(f, "LD V{:X}, {:#04X}", x, byte),
+            Instruction::AddByte { x, byte } => write!(f, "ADD V{:X}, {:#04X}", x, byte),
+            Instruction::Set { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::Sub { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShiftRight { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubReverse { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShiftLeft { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SkipNotEqual { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::SetI { address } => write!(f, "LD I, {:#05X}", address),
+            Instruction::JumpOffset { address, .. } => write!(f, "JP V0, {:#05X}", address),
+            Instruction::Random { x, byte } => write!(f, "RND V{:X}, {:#04X}", x, byte),
+            Instruction::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {:#03X}", x, y, n),
+            Instruction::SkipKeyPressed { x } => write!(f, "SKP V{:X}", x),
+            Instruction::SkipKeyNotPressed { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::SetILong { address } => write!(f, "LD I, {:#06X}", address),
+            Instruction::SelectPlanes { n } => write!(f, "PLANE {:#03X}", n),
+            Instruction::LoadAudio => write!(f, "AUDIO"),
+            Instruction::GetDelay { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::SetDelay { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::SetSound { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddI { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::Font { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::BigFont { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::Bcd { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::SetPitch { x } => write!(f, "LD PITCH, V{:X}", x),
+            Instruction::Store { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::Load { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::StoreFlags { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown { opcode } => write!(f, "DW {:#06X}", opcode)
+        }
+    }
+}
@@ -10,6 +10,7 @@ for more details. You should have received a copy of the GNU Affero General Publ
 not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::collections::VecDeque;
 use std::path::Path;
 use std::fs::File;
 use std::fs;
@@ -17,20 +18,69 @@ use std::io::{Read, stdout, Write};
 use std::time::Duration;
 use chrono::Utc;
 
-use crossterm::terminal::{Clear,ClearType,enable_raw_mode, disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::terminal::{self,Clear,ClearType,enable_raw_mode, disable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
 use crossterm::{execute, queue, style};
 use crossterm::cursor::{Hide,Show,MoveTo};
 use crossterm::event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers};
 
-use rodio::{OutputStream,Sink,source};
+use rodio::{OutputStream,Sink};
+use std::sync::Arc;
 
-use crate::chip8::DebugInfo;
+use crate::audio::{AudioState, PatternWave};
+use crate::palette::Palette;
+use crate::render::{Cell, RenderMode};
+use chip8::chip8::{CpuError, DebugInfo, MEMORY_SIZE, PROGRAM_START};
+use chip8::debugger::Hit;
+use chip8::{disasm, octo, Audio, Display, Input};
+
+//Number of lines of command output shown above the console prompt
+const CONSOLE_LINES: usize = 8;
+
+//Frames a key stays pressed after a key press is read from the terminal, about 100 ms
+const KEY_HOLD_FRAMES: u8 = 6;
+
+/*The memory and disassembly panes are drawn beside the screen, one above the other. Written bytes stay highlighted
+for a number of frames*/
+const PANE_GAP: usize = 2;
+const PANE_WIDTH: usize = 56;
+const MEMORY_ROWS: usize = 15;
+const DISASSEMBLY_ROWS: usize = 15;
+const WRITE_HIGHLIGHT_FRAMES: u8 = 30;
+const PANES_HEIGHT: usize = MEMORY_ROWS + DISASSEMBLY_ROWS + 3;
+
+//Rows below the screen for the step, halted, breakpoint and status lines and the console, without the debug information
+const STATUS_ROWS: usize = 6 + CONSOLE_LINES;
+const INFO_WIDTH: usize = 76;
 
 pub struct Engine {
+    /*Resolution of the framebuffer, the size of the terminal, and the columns and rows of cells the screen takes up
+    on the terminal once it is scaled*/
+    render_mode: RenderMode,
+    palette: Palette,
+    resolution: (usize, usize),
+    terminal_size: (usize, usize),
+    scale: usize,
+    screen_width: usize,
+    screen_height: usize,
+    too_small: bool,
+    //The lines of information below the screen, kept to draw them again after the layout changes
+    info_lines: Vec<String>,
+    //The cells on the terminal, so that only changed cells are drawn
+    last_frame: Vec<Cell>,
+    //The output stream has to be kept alive for the sink to play
+    _sound_stream: OutputStream,
     sound_sink: Sink,
-    beep_timer: u8,
+    audio_state: Arc<AudioState>,
     keys: [char; 16],
-    key_timer: [u8; 16]
+    key_timer: [u8; 16],
+    //The debugger console line being typed while the console is open, and the most recent output
+    console: Option<String>,
+    console_output: VecDeque<String>,
+    //First address shown in the memory pane, and the memory when it was last drawn to find written bytes
+    memory_view: usize,
+    last_memory: Vec<u8>,
+    write_age: Vec<u8>,
+    panes_shown: bool
 }
 
 pub struct KeyActions {
@@ -38,76 +88,212 @@ pub struct KeyActions {
     pub next_step: bool,
     pub step: bool,
     pub debug: bool,
-    pub mem_dump: bool
+    pub mem_dump: bool,
+    pub pause: bool,
+    pub turbo: bool,
+    pub slow_motion: bool,
+    pub save_state: bool,
+    pub load_state: bool,
+    pub next_slot: bool,
+    pub previous_slot: bool,
+    pub rewind: bool,
+    pub memory_view_i: bool,
+    pub command: Option<String>
 }
 
 impl Engine {
 
-    //Draws the chip8 graphics screen
-    pub fn draw (&mut self, gfx: [u8; 2048]){
+    /*Works out the scale of the screen from the size of the terminal and clears the terminal. The screen is scaled
+    up by the largest integer factor that leaves room for the status lines below it, unless the panes are shown beside
+    it. The screen is drawn in full on the next frame*/
+    fn layout(&mut self){
+        let (width, height) = self.resolution;
+        let (columns, rows) = self.terminal_size;
+        let (min_width, min_height) = self.render_mode.screen_size(width, height);
+        self.too_small = min_width > columns || min_height > rows;
+        self.scale = 1;
+        if !self.panes_shown {
+            loop {
+                let (scaled_width, scaled_height) = self.render_mode.screen_size(width*(self.scale+1), height*(self.scale+1));
+                if scaled_width > columns || scaled_height + STATUS_ROWS > rows {
+                    break;
+                }
+                self.scale += 1;
+            }
+        }
+        let (screen_width, screen_height) = self.render_mode.screen_size(width*self.scale, height*self.scale);
+        self.screen_width = screen_width;
+        self.screen_height = screen_height;
+        self.last_frame.clear();
+
+        let mut stdout = stdout();
+        let _r = queue!(stdout,Clear(ClearType::All));
+        if self.too_small {
+            //The message is centred and cut to fit however small the terminal is
+            let message = ["Terminal too small".to_string(),
+                format!("Need {}x{}, have {}x{}",min_width,min_height,columns,rows)];
+            let top = rows.saturating_sub(message.len())/2;
+            for (row, line) in message.iter().enumerate().take(rows) {
+                let line: String = line.chars().take(columns).collect();
+                let column = (columns - line.chars().count())/2;
+                let _r = queue!(stdout,MoveTo(column as u16, (top + row) as u16),style::Print(line));
+            }
+        } else {
+            self.print_info(&mut stdout);
+        }
+        stdout.flush().unwrap();
+    }
+
+    //Prints the lines of information below the screen, or below the panes when they are taller, cut to the terminal
+    fn print_info(&self, stdout: &mut std::io::Stdout){
+        let top = if self.panes_shown { self.screen_height.max(PANES_HEIGHT-1) } else { self.screen_height };
+        let (columns, rows) = self.terminal_size;
+        for (row, line) in (top..rows).zip(&self.info_lines) {
+            let line: String = format!("{:<1$}",line,INFO_WIDTH).chars().take(columns).collect();
+            let _r = queue!(stdout,MoveTo(0, row as u16),style::Print(line));
+        }
+    }
+
+    /*Draws the memory pane, highlighting the bytes at PC and I and recently written bytes, and the disassembly
+    pane centred on PC beside the screen. Showing or hiding the panes changes the layout, and they are only drawn
+    when they fit on the terminal*/
+    pub fn panes_draw(&mut self, memory: &[u8], pc: u16, i: u16, visible: bool){
+        if visible != self.panes_shown {
+            self.panes_shown = visible;
+            self.layout();
+        }
+        let (columns, rows) = self.terminal_size;
+        if !visible || self.too_small || self.screen_width + PANE_GAP + PANE_WIDTH > columns || PANES_HEIGHT > rows {
+            return;
+        }
+
+        //Bytes that changed since the last frame are written bytes
+        if self.last_memory.len() != memory.len() {
+            self.last_memory = memory.to_vec();
+        }
+        for ((age, last), &byte) in self.write_age.iter_mut().zip(self.last_memory.iter_mut()).zip(memory) {
+            if *last != byte {
+                *last = byte;
+                *age = WRITE_HIGHLIGHT_FRAMES;
+            } else if *age > 0 {
+                *age -= 1;
+            }
+        }
+
         let mut stdout = stdout();
-        let _r = queue!(stdout,MoveTo(0, 0));
-        for y in 0..32 {
-            for x in 0..64 {
-                if gfx[(y*64)+x] != 0 {
-                    let _r = queue!(stdout,style::Print("#"));
+        let column = (self.screen_width+PANE_GAP) as u16;
+        let (pc, i) = (pc as usize, i as usize);
+
+        let _r = queue!(stdout,MoveTo(column, 0),style::Print(format!("{:<1$}","Memory (Shift+Up/Down/PgUp/PgDn, Home: I)",PANE_WIDTH)));
+        for row in 0..MEMORY_ROWS {
+            let address = self.memory_view + row*16;
+            let _r = queue!(stdout,MoveTo(column, row as u16 + 1));
+            if address >= memory.len() {
+                let _r = queue!(stdout,style::Print(format!("{:<1$}","",PANE_WIDTH)));
+                continue;
+            }
+            let _r = queue!(stdout,style::Print(format!("{:#06X} ",address)));
+            for (offset, byte) in memory[address..address+16].iter().enumerate() {
+                let location = address + offset;
+                let text = style::style(format!(" {:02X}",byte));
+                let text = if location == pc || location == pc+1 {
+                    text.with(style::Color::Black).on(style::Color::Green)
+                } else if location == i {
+                    text.with(style::Color::Black).on(style::Color::Yellow)
+                } else if self.write_age[location] > 0 {
+                    text.with(style::Color::Red)
                 } else {
-                    let _r = queue!(stdout,style::Print(" "));
-                }
+                    text
+                };
+                let _r = queue!(stdout,style::PrintStyledContent(text));
             }
-            let _r = queue!(stdout,style::Print("\r\n"));  
+            let _r = queue!(stdout,style::Print(" "));
+        }
+
+        //Instructions before PC are assumed to be 2 bytes long since memory can not be disassembled backwards
+        let _r = queue!(stdout,MoveTo(column, MEMORY_ROWS as u16 + 1),style::Print(format!("{:<1$}","",PANE_WIDTH)));
+        let _r = queue!(stdout,MoveTo(column, MEMORY_ROWS as u16 + 2),style::Print(format!("{:<1$}","Disassembly",PANE_WIDTH)));
+        let before = DISASSEMBLY_ROWS/2;
+        let mut address = pc.saturating_sub(before*2);
+        for row in 0..DISASSEMBLY_ROWS {
+            let (line, len) = disasm::instruction_line(memory, address);
+            let marker = if address == pc { ">" } else { " " };
+            let line = format!("{} {:<2$}",marker,line,PANE_WIDTH-2);
+            let line = if address == pc { style::style(line).with(style::Color::Green) } else { style::style(line) };
+            let _r = queue!(stdout,MoveTo(column, (MEMORY_ROWS + 3 + row) as u16),style::PrintStyledContent(line));
+            address += len;
         }
 
         stdout.flush().unwrap();
     }
-    
+
     //Draws the debug information and step information
-    pub fn info_draw(&mut self, debug_info: DebugInfo, debug: bool, step: bool){
-        let mut stdout = stdout();
-        let _r = queue!(stdout,MoveTo(0, 32));
-        let _r = queue!(stdout,style::Print("\r\n"));
+    pub fn info_draw(&mut self, debug_info: &DebugInfo, debug: bool, step: bool, halted: Option<&CpuError>, hit: Option<&Hit>, status: &str){
+        let mut lines = vec!["".to_string()];
         if debug {
-            let _r = queue!(stdout,style::Print(format!("Opcode: {:#06X} {:<32}\r\n",debug_info.opcode,debug_info.opcode_trans)));
-            let _r = queue!(stdout,style::Print(format!("Program Counter: {:#06X}\r\n",debug_info.pc)));
-            let _r = queue!(stdout,style::Print(format!("I: {:#06X}\r\n",debug_info.i)));
-            let _r = queue!(stdout,style::Print(format!("Stack Pointer: {:#06X}\r\n",debug_info.sp)));
-            let _r = queue!(stdout,style::Print(format!("Delay Timer: {:#04X}\r\n",debug_info.delay_tmr)));
-            let _r = queue!(stdout,style::Print(format!("Sound Timer: {:#04X}\r\n",debug_info.sound_tmr)));
+            lines.push(format!("Opcode: {:#06X} {}",debug_info.opcode,debug_info.opcode_trans));
+            lines.push(format!("Program Counter: {:#06X}",debug_info.pc));
+            lines.push(format!("I: {:#06X}",debug_info.i));
+            lines.push(format!("Stack Pointer: {:#06X}",debug_info.sp));
+            lines.push(format!("Delay Timer: {:#04X}",debug_info.delay_tmr));
+            lines.push(format!("Sound Timer: {:#04X}",debug_info.sound_tmr));
             for num in 0..16 {
-                let _r = queue!(stdout,style::Print(format!("V{:X} : {:#04X} Keypad{:X}: {:#04X} Stack{:X} : {:#06X}\r\n",num,debug_info.v[num],num,debug_info.keypad[num],num,debug_info.stack[num])));
-            }
-        } else {
-            for _y in 0..22 {
-                for _x in 0..50 {
-                    let _r = queue!(stdout,style::Print(" "));
-                }
-                let _r = queue!(stdout,style::Print("\r\n"));
+                lines.push(format!("V{:X} : {:#04X} Keypad{:X}: {:#04X} Stack{:X} : {:#06X}",num,debug_info.v[num],num,debug_info.keypad[num],num,debug_info.stack[num]));
             }
         }
         if step {
-            let _r = queue!(stdout,style::Print("Press Down to step to next instruction...\r\n"));
+            lines.push("Press Down to step to next instruction...".to_string());
         } else {
-            for _x in 0..50 {
-                let _r = queue!(stdout,style::Print(" "));
-            }
-            let _r = queue!(stdout,style::Print("\r\n"));
+            lines.push("".to_string());
+        }
+        match halted {
+            Some(error) => lines.push(format!("CPU halted: {}",error)),
+            None => lines.push("".to_string())
+        }
+        match hit {
+            Some(hit) => lines.push(hit.to_string()),
+            None => lines.push("".to_string())
+        }
+        lines.push(status.to_string());
+        for line in 0..CONSOLE_LINES {
+            let output = match &self.console {
+                Some(_) => self.console_output.get(line).map_or("", |output| output.as_str()),
+                None => ""
+            };
+            lines.push(output.to_string());
+        }
+        match &self.console {
+            Some(command) => lines.push(format!("> {}_",command)),
+            None => lines.push("".to_string())
         }
 
+        //Lines left over from the debug information are cleared when it is hidden
+        let shown = self.info_lines.len();
+        self.info_lines = lines;
+        if self.too_small {
+            return;
+        }
+        let mut stdout = stdout();
+        let (columns, rows) = self.terminal_size;
+        let top = if self.panes_shown { self.screen_height.max(PANES_HEIGHT-1) } else { self.screen_height };
+        for row in (top + self.info_lines.len()..top + shown).take_while(|&row| row < rows) {
+            let _r = queue!(stdout,MoveTo(0, row as u16),style::Print(format!("{:<1$}","",INFO_WIDTH.min(columns))));
+        }
+        self.print_info(&mut stdout);
         stdout.flush().unwrap();
     }
    
     pub fn input(&mut self, keypad: &mut [u8; 16]) -> KeyActions {
         /*When a key is dectected as pressed a timer is used to keep the correponding key variable
-        in the pressed value for a number of cycles since otherwise the CPU will not reliably
-        dectect when the key is being pressed*/
-        for i in 0..self.key_timer.len(){
-            if self.key_timer[i] > 0 {
-                self.key_timer[i]-=1;
-                if self.key_timer[i] == 0 {
-                    keypad[i] = 0;
+        in the pressed value for a number of frames since the terminal only reports key presses, not
+        releases, and otherwise the CPU will not reliably dectect when the key is being pressed*/
+        for (key, timer) in keypad.iter_mut().zip(self.key_timer.iter_mut()) {
+            if *timer > 0 {
+                *timer -= 1;
+                if *timer == 0 {
+                    *key = 0;
                 }
-            } 
-             
+            }
         }
 
         let mut key_actions = KeyActions {
@@ -116,6 +302,16 @@ impl Engine {
             step: false,
             debug: false,
             mem_dump: false,
+            pause: false,
+            turbo: false,
+            slow_motion: false,
+            save_state: false,
+            load_state: false,
+            next_slot: false,
+            previous_slot: false,
+            rewind: false,
+            memory_view_i: false,
+            command: None
         };
 
         for _x in 0..1 {
@@ -123,8 +319,16 @@ impl Engine {
                 if has_event{
                     if let Ok(current_event) = read(){
                         match current_event {
+                            Event::Key(event) if self.console.is_some() => {
+                                key_actions.command = self.console_input(event);
+                            }
+                            Event::Key(event) if event.modifiers == KeyModifiers::SHIFT && self.scroll_memory(event.code) => {}
                             Event::Key(event) => {
-                                if event == KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE) {
+                                if event == KeyEvent::new(KeyCode::Home, KeyModifiers::NONE) {
+                                    key_actions.memory_view_i = true;
+                                } else if event == KeyEvent::new(KeyCode::Char('`'), KeyModifiers::NONE) {
+                                    self.console = Some("".to_string());
+                                } else if event == KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE) {
                                     key_actions.exit = true;
                                 } else if event == KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE){
                                     key_actions.step = true;
@@ -134,18 +338,37 @@ impl Engine {
                                     key_actions.debug = true;
                                 }else if event == KeyEvent::new(KeyCode::End, KeyModifiers::NONE){
                                     key_actions.mem_dump = true;
+                                } else if event == KeyEvent::new(KeyCode::Char(' '), KeyModifiers::NONE){
+                                    key_actions.pause = true;
+                                } else if event == KeyEvent::new(KeyCode::Right, KeyModifiers::NONE){
+                                    key_actions.turbo = true;
+                                } else if event == KeyEvent::new(KeyCode::Left, KeyModifiers::NONE){
+                                    key_actions.slow_motion = true;
+                                } else if event == KeyEvent::new(KeyCode::F(5), KeyModifiers::NONE){
+                                    key_actions.save_state = true;
+                                } else if event == KeyEvent::new(KeyCode::F(9), KeyModifiers::NONE){
+                                    key_actions.load_state = true;
+                                } else if event == KeyEvent::new(KeyCode::PageUp, KeyModifiers::NONE){
+                                    key_actions.next_slot = true;
+                                } else if event == KeyEvent::new(KeyCode::PageDown, KeyModifiers::NONE){
+                                    key_actions.previous_slot = true;
+                                } else if event == KeyEvent::new(KeyCode::Backspace, KeyModifiers::NONE){
+                                    key_actions.rewind = true;
                                 }else {
-                                    for key in 0..keypad.len() {
-                                        if event == KeyEvent::new(KeyCode::Char(self.keys[key]), KeyModifiers::NONE){
+                                    for (key, &key_char) in self.keys.iter().enumerate() {
+                                        if event == KeyEvent::new(KeyCode::Char(key_char), KeyModifiers::NONE){
                                             keypad[key] = 1;
-                                            self.key_timer[key] = 25;
+                                            self.key_timer[key] = KEY_HOLD_FRAMES;
                                         }
                                     }
                                 }
 
                             }
                             Event::Mouse(_event) => {}
-                            Event::Resize(_x,_y) => {}
+                            Event::Resize(columns,rows) => {
+                                self.terminal_size = (columns as usize, rows as usize);
+                                self.layout();
+                            }
                         }
                     }
                 }
@@ -153,73 +376,194 @@ impl Engine {
             } 
         }
 
-        return key_actions;
-        
+        key_actions
+    }
+
+    //Scrolls the memory pane by a row or a page, returns false for keys that do not scroll
+    fn scroll_memory(&mut self, code: KeyCode) -> bool {
+        let last_view = MEMORY_SIZE - MEMORY_ROWS*16;
+        self.memory_view = match code {
+            KeyCode::Up => self.memory_view.saturating_sub(16),
+            KeyCode::Down => (self.memory_view + 16).min(last_view),
+            KeyCode::PageUp => self.memory_view.saturating_sub(MEMORY_ROWS*16),
+            KeyCode::PageDown => (self.memory_view + MEMORY_ROWS*16).min(last_view),
+            _ => return false
+        };
+        true
     }
 
-    //TODO: fix sound, currently does not beep when requested
-    pub fn sound (&mut self, beep: &mut bool){
-        if *beep {
-            self.sound_sink.play();
-            self.beep_timer = 25;
-            *beep = false;
+    //Scrolls the memory pane to show an address in its top row
+    pub fn memory_view_to(&mut self, address: u16) {
+        self.memory_view = (address as usize & !0xF).min(MEMORY_SIZE - MEMORY_ROWS*16);
+    }
+
+    //Edits the console line while the console is open, returns the command when Enter is pressed
+    fn console_input(&mut self, event: KeyEvent) -> Option<String> {
+        let command = self.console.as_mut()?;
+        match event.code {
+            KeyCode::Esc | KeyCode::Char('`') => self.console = None,
+            KeyCode::Enter => return Some(std::mem::take(command)),
+            KeyCode::Backspace => {
+                command.pop();
+            }
+            KeyCode::Char(c) if event.modifiers == KeyModifiers::NONE || event.modifiers == KeyModifiers::SHIFT => {
+                command.push(c);
+            }
+            _ => {}
         }
-        if self.beep_timer > 0 {
-            self.beep_timer -= 1;
-            if self.beep_timer == 0 {
-                self.sound_sink.pause();
+        None
+    }
+
+    //Adds lines of output to the console, only the most recent lines are kept
+    pub fn console_print(&mut self, lines: Vec<String>) {
+        for line in lines {
+            if self.console_output.len() == CONSOLE_LINES {
+                self.console_output.pop_front();
             }
+            self.console_output.push_back(line);
         }
     }
-    
+
     pub fn deinit(self) {
         let _r = execute!(stdout(),Show,LeaveAlternateScreen);
         disable_raw_mode().unwrap();
     }
 }
 
-pub fn init() -> Engine {
+impl Display for Engine {
+    //Draws the chip8 graphics screen at whichever resolution is active, packed into cells by the render mode
+    fn draw (&mut self, gfx: &[u8], width: usize, height: usize){
+        let mut stdout = stdout();
+        if (width, height) != self.resolution {
+            //The resolution changed so lay out the terminal again for the new resolution
+            self.resolution = (width, height);
+            self.layout();
+        }
+        if self.too_small {
+            return;
+        }
+        /*Only the cells that changed since the last frame are printed, the cursor is only moved when the next changed
+        cell does not follow the last one printed*/
+        let cells = self.render_mode.render(gfx, width, height, self.scale);
+        let mut cursor = None;
+        for (index, cell) in cells.iter().enumerate() {
+            if self.last_frame.get(index) == Some(cell) {
+                continue;
+            }
+            let (column, row) = (index % self.screen_width, index / self.screen_width);
+            if cursor != Some(index) {
+                let _r = queue!(stdout,MoveTo(column as u16, row as u16));
+            }
+            //Blank cells only need a colour when the palette has a background colour
+            if cell.foreground != 0 || cell.background != 0 || self.palette.color(0) != style::Color::Reset {
+                let symbol = style::style(cell.symbol).with(self.palette.color(cell.foreground)).on(self.palette.color(cell.background));
+                let _r = queue!(stdout,style::PrintStyledContent(symbol));
+            } else {
+                let _r = queue!(stdout,style::Print(cell.symbol));
+            }
+            //The cursor does not move to the next row after the last column is printed
+            cursor = if column + 1 < self.screen_width { Some(index + 1) } else { None };
+        }
+        self.last_frame = cells;
+
+        stdout.flush().unwrap();
+    }
+}
+
+impl Input for Engine {
+    /*Only updates the keypad, the emulator control keys such as Esc are thrown away. The terminal emulator loop calls
+    Engine::input instead to act on them, so the engine can not be driven by run_frame alone*/
+    fn update_keypad(&mut self, keypad: &mut [u8; 16]) {
+        self.input(keypad);
+    }
+}
+
+impl Audio for Engine {
+    //Updates the audio pattern played by the sound sink, which is silent while the sound timer is stopped
+    fn sound (&mut self, playing: bool, pattern: [u8; 16], pitch: u8){
+        self.audio_state.update(playing, pattern, pitch);
+    }
+}
+
+pub fn init(render_mode: RenderMode, palette: Palette) -> Engine {
     let _r = execute!(stdout(),EnterAlternateScreen,Hide,Clear(ClearType::All));
     enable_raw_mode().unwrap();
     
-    let engine = Engine {
-        sound_sink: Sink::try_new(&OutputStream::try_default().unwrap().1).unwrap(),
-        beep_timer: 0,
+    let (sound_stream, sound_handle) = OutputStream::try_default().unwrap();
+    let (columns, rows) = terminal::size().unwrap_or((80, 24));
+    let mut engine = Engine {
+        render_mode,
+        palette,
+        resolution: (64, 32),
+        terminal_size: (columns as usize, rows as usize),
+        scale: 1,
+        screen_width: 0,
+        screen_height: 0,
+        too_small: false,
+        info_lines: Vec::new(),
+        last_frame: Vec::new(),
+        _sound_stream: sound_stream,
+        sound_sink: Sink::try_new(&sound_handle).unwrap(),
+        audio_state: Arc::new(AudioState::new()),
         keys: ['x','1','2','3'
         ,'q','w','e','a'
         ,'s','d','z','c'
         ,'4','r','f','v'],
-        key_timer: [0; 16]
+        key_timer: [0; 16],
+        console: None,
+        console_output: VecDeque::with_capacity(CONSOLE_LINES),
+        memory_view: PROGRAM_START,
+        last_memory: Vec::new(),
+        write_age: vec![0; MEMORY_SIZE],
+        panes_shown: false
     };
-    engine.sound_sink.append(source::SineWave::new(500));
-    engine.sound_sink.pause();
+    engine.sound_sink.append(PatternWave::new(engine.audio_state.clone()));
+    engine.layout();
 
-    return engine;
+    engine
 }
 
 
 
-pub fn load_rom(file: &str) -> [u8; 3584] {
-    let mut rom = [0; 3584];
+//Loads a ROM image, or assembles it first if it is Octo source with the .8o extension
+pub fn load_rom(file: &str) -> Vec<u8> {
+    let max_size = MEMORY_SIZE - PROGRAM_START;
     let p = &("roms/".to_owned()+file); 
     let path = Path::new(p);
     let display = path.display();
 
-    let mut file = match File::open(&path) {
+    if file.ends_with(".8o") {
+        let source = match fs::read_to_string(path) {
+            Err(why) => panic!("couldn't read {}: {}", display, why),
+            Ok(source) => source,
+        };
+        return match octo::assemble(&source) {
+            Ok(rom) => rom,
+            Err(error) => {
+                //Assembly errors are mistakes in the program rather than the emulator, so report them without a panic
+                eprintln!("{}:{}", display, error);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    let mut file = match File::open(path) {
         Err(why) => panic!("couldn't open {}: {}", display, why),
         Ok(file) => file,
     };
 
     let size = file.metadata().unwrap().len();
-    if size > 3584 {
-        panic!("ROM too big: {} Bytes out of max 3584 Bytes",size);
-    } else {
-        let _n = file.read(&mut rom);
+    if size > max_size as u64 {
+        panic!("ROM too big: {} Bytes out of max {} Bytes",size,max_size);
+    }
+    let mut rom = Vec::with_capacity(size as usize);
+    if let Err(why) = file.read_to_end(&mut rom) {
+        panic!("couldn't read {}: {}", display, why);
     }
-    return rom;
+    rom
 }
 
-pub fn write_mem_dump_file(mem_dump: [u8; 4096]){
+pub fn write_mem_dump_file(mem_dump: &[u8]){
     let date = Utc::now().timestamp();
 
     match fs::create_dir_all("memory_dumps") {
@@ -231,15 +575,15 @@ pub fn write_mem_dump_file(mem_dump: [u8; 4096]){
     let path = Path::new(&path);
     let display = path.display();
 
-    let mut file = match File::create(&path) {
+    let mut file = match File::create(path) {
         Err(why) => panic!("couldn't create {}: {}", display, why),
         Ok(file) => file,
     };
 
     let mut to_write = "".to_owned();
 
-    for loc in (0..4096).step_by(2) {
-        to_write.push_str(&format!("{:#05X} {:#04X} {:#04X}\r\n",loc,mem_dump[loc],mem_dump[loc+1]));
+    for loc in (0..mem_dump.len()).step_by(2) {
+        to_write.push_str(&format!("{:#06X} {:#04X} {:#04X}\r\n",loc,mem_dump[loc],mem_dump[loc+1]));
     }
 
     match file.write_all(to_write.as_bytes()) {
@@ -248,8 +592,17 @@ pub fn write_mem_dump_file(mem_dump: [u8; 4096]){
     }
 }
 
+//Save states are named after the ROM file and slot, so each ROM has its own set of slots
+fn save_state_path(rom: &str, slot: u8) -> String {
+    let name = Path::new(rom).file_name().map_or(rom.into(), |name| name.to_string_lossy());
+    format!("save_states/{}.{}.state",name,slot)
+}
 
+pub fn write_save_state_file(rom: &str, slot: u8, state: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all("save_states")?;
+    fs::write(save_state_path(rom, slot), state)
+}
 
-
-
-
+pub fn read_save_state_file(rom: &str, slot: u8) -> std::io::Result<Vec<u8>> {
+    fs::read(save_state_path(rom, slot))
+}
@@ -0,0 +1,33 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*The emulator core and its tools, without the terminal front-end. Front-ends implement the Display, Input and Audio
+traits in the frontend module to run the machine*/
+
+pub mod chip8;
+pub mod instruction;
+pub mod quirks;
+pub mod disasm;
+pub mod octo;
+pub mod savestate;
+pub mod rewind;
+pub mod debugger;
+pub mod console;
+pub mod gdb;
+pub mod trace;
+pub mod headless;
+pub mod frontend;
+pub mod filter;
+
+pub use crate::chip8::{init, Chip8, CpuError, StepOutcome, MEMORY_SIZE, PROGRAM_START};
+pub use crate::frontend::{Audio, Display, Input};
+pub use crate::quirks::Quirks;
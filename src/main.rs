@@ -10,10 +10,25 @@ for more details. You should have received a copy of the GNU Affero General Publ
 not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::env;
+
 mod emulator;
-mod chip8;
 mod io;
+mod audio;
+mod palette;
+mod render;
+
+use chip8::disasm;
 
 fn main(){
-    emulator::emulator_loop();
+    let args: Vec<String> = env::args().collect();
+    if args.len() > 1 && args[1] == "disasm" {
+        if args.len() < 3 {
+            panic!("Error: Need to specify program location after disasm");
+        }
+        let rom = io::load_rom(&args[2]);
+        print!("{}", disasm::disassemble(&rom).listing());
+    } else {
+        emulator::emulator_loop();
+    }
 }
\ No newline at end of file
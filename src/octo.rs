@@ -0,0 +1,758 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+
+use crate::chip8::{MEMORY_SIZE, PROGRAM_START};
+
+//Nested macro expansions beyond this depth are assumed to be infinite recursion
+const MAX_MACRO_DEPTH: usize = 64;
+
+//Assembly errors are reported at the line and column of the token that caused them, both counted from 1
+#[derive(Clone, PartialEq, Debug)]
+pub struct AssemblyError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AssemblyError {}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+    depth: usize //How many macro expansions produced this token
+}
+
+impl Token {
+    fn error(&self, message: String) -> AssemblyError {
+        AssemblyError { line: self.line, column: self.column, message }
+    }
+}
+
+#[derive(Clone)]
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>
+}
+
+//Ways a label address is written into an instruction once the label is defined
+#[derive(Clone, Copy)]
+enum FixupKind {
+    Address,     //The low 12 bits of an opcode
+    LongAddress, //A full 16 bit word
+    UnpackHigh,  //The low 4 bits of a byte, from bits 8-11 of the address
+    UnpackLow    //A full byte, from the low 8 bits of the address
+}
+
+struct Fixup {
+    address: usize,
+    kind: FixupKind,
+    token: Token
+}
+
+//An operand is either a register or a number
+#[derive(Clone, Copy, PartialEq)]
+enum Operand {
+    Register(u8),
+    Number(i64)
+}
+
+//A condition compiles to instructions that set up VF, then a skip taken when the condition is true or false
+struct Condition {
+    prelude: Vec<u16>,
+    skip_if_true: u16,
+    skip_if_false: u16
+}
+
+struct Loop {
+    start: u16,
+    exits: Vec<usize>,
+    token: Token
+}
+
+struct Branch {
+    jump: usize,
+    token: Token
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    last: Token,
+    memory: Vec<u8>,
+    address: usize,
+    end: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    fixups: Vec<Fixup>,
+    loops: Vec<Loop>,
+    branches: Vec<Branch>
+}
+
+/*Assembles Octo source into a ROM image that is loaded at 0x200. Programs with a main label start with a jump to
+it, as in Octo*/
+pub fn assemble(source: &str) -> Result<Vec<u8>, AssemblyError> {
+    let tokens = tokenize(source);
+    let last = tokens.last().cloned().unwrap_or(Token { text: "".to_string(), line: 1, column: 1, depth: 0 });
+    let has_main = tokens.windows(2).any(|pair| pair[0].text == ":" && pair[1].text == "main");
+
+    let mut assembler = Assembler {
+        tokens: tokens.into(),
+        last,
+        memory: vec![0; MEMORY_SIZE],
+        address: PROGRAM_START,
+        end: PROGRAM_START,
+        labels: HashMap::new(),
+        constants: HashMap::new(),
+        aliases: HashMap::new(),
+        macros: HashMap::new(),
+        fixups: Vec::new(),
+        loops: Vec::new(),
+        branches: Vec::new()
+    };
+
+    if has_main {
+        let main = Token { text: "main".to_string(), line: 1, column: 1, depth: 0 };
+        assembler.emit_address(0x1000, &main)?;
+    }
+    while !assembler.tokens.is_empty() {
+        assembler.statement()?;
+    }
+    assembler.finish()
+}
+
+//Splits the source into whitespace separated tokens, dropping comments that run from # to the end of the line
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line_num, line) in source.lines().enumerate() {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line
+        };
+        let mut start = None;
+        for (column, c) in line.char_indices().chain(std::iter::once((line.len(), ' '))) {
+            if c.is_whitespace() {
+                if let Some(start_column) = start.take() {
+                    tokens.push(Token {
+                        text: line[start_column..column].to_string(),
+                        line: line_num + 1,
+                        column: line[..start_column].chars().count() + 1,
+                        depth: 0
+                    });
+                }
+            } else if start.is_none() {
+                start = Some(column);
+            }
+        }
+    }
+    tokens
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text)
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits.strip_prefix("0b").or_else(|| digits.strip_prefix("0B")) {
+        i64::from_str_radix(binary, 2).ok()?
+    } else {
+        digits.parse().ok()?
+    };
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let mut chars = text.chars();
+    match (chars.next(), chars.next(), chars.next()) {
+        (Some('v'), Some(digit), None) | (Some('V'), Some(digit), None) => digit.to_digit(16).map(|reg| reg as u8),
+        _ => None
+    }
+}
+
+//Words that can not be used as names for labels, constants, aliases or macros
+fn is_reserved(name: &str) -> bool {
+    const RESERVED: [&str; 42] = [
+        ":=", "+=", "-=", "=-", "|=", "&=", "^=", ">>=", "<<=", "==", "!=", "<", ">", "<=", ">=", "key", "-key",
+        "hex", "bighex", "random", "delay", "buzzer", "pitch", "long", "i", "if", "then", "begin", "else", "end",
+        "loop", "again", "while", "clear", "return", ";", "sprite", "jump", "jump0", "save", "load", "bcd"
+    ];
+    RESERVED.contains(&name) || parse_register(name).is_some() || parse_number(name).is_some() || name.starts_with(':')
+}
+
+impl Assembler {
+    fn next(&mut self) -> Result<Token, AssemblyError> {
+        match self.tokens.pop_front() {
+            Some(token) => {
+                self.last = token.clone();
+                Ok(token)
+            }
+            None => Err(self.last.error("Unexpected end of file".to_string()))
+        }
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, text: &str) -> Result<Token, AssemblyError> {
+        let token = self.next()?;
+        if token.text != text {
+            return Err(token.error(format!("Expected '{}' but found '{}'", text, token.text)));
+        }
+        Ok(token)
+    }
+
+    fn name(&mut self) -> Result<Token, AssemblyError> {
+        let token = self.next()?;
+        if is_reserved(&token.text) {
+            return Err(token.error(format!("'{}' can not be used as a name", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn emit_byte(&mut self, byte: u8, token: &Token) -> Result<(), AssemblyError> {
+        if self.address >= MEMORY_SIZE {
+            return Err(token.error("Program does not fit in memory".to_string()));
+        }
+        self.memory[self.address] = byte;
+        self.address += 1;
+        self.end = self.end.max(self.address);
+        Ok(())
+    }
+
+    fn emit(&mut self, opcode: u16, token: &Token) -> Result<(), AssemblyError> {
+        self.emit_byte((opcode >> 8) as u8, token)?;
+        self.emit_byte(opcode as u8, token)
+    }
+
+    //Emits an opcode whose low 12 bits are the address named by the token, which may be a label defined later
+    fn emit_address(&mut self, opcode: u16, token: &Token) -> Result<(), AssemblyError> {
+        match self.value(token)? {
+            Some(address) => {
+                if !(0..=0xFFF).contains(&address) {
+                    return Err(token.error(format!("Address {:#X} does not fit in 12 bits", address)));
+                }
+                self.emit(opcode | address as u16, token)
+            }
+            None => {
+                self.fixups.push(Fixup { address: self.address, kind: FixupKind::Address, token: token.clone() });
+                self.emit(opcode, token)
+            }
+        }
+    }
+
+    //Value of a number, constant or label, None if the token names a label that is not defined yet
+    fn value(&self, token: &Token) -> Result<Option<i64>, AssemblyError> {
+        if let Some(number) = parse_number(&token.text) {
+            Ok(Some(number))
+        } else if let Some(constant) = self.constants.get(&token.text) {
+            Ok(Some(*constant))
+        } else if let Some(label) = self.labels.get(&token.text) {
+            Ok(Some(*label as i64))
+        } else if is_reserved(&token.text) {
+            Err(token.error(format!("Expected a value but found '{}'", token.text)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    //Value that has to be known when it is used, such as the operands of :const or :org
+    fn known_value(&self, token: &Token) -> Result<i64, AssemblyError> {
+        match self.value(token)? {
+            Some(value) => Ok(value),
+            None => Err(token.error(format!("Undefined name '{}'", token.text)))
+        }
+    }
+
+    fn ranged_value(&self, token: &Token, min: i64, max: i64) -> Result<i64, AssemblyError> {
+        let value = self.known_value(token)?;
+        if value < min || value > max {
+            return Err(token.error(format!("Value {} is out of range {} to {}", value, min, max)));
+        }
+        Ok(value)
+    }
+
+    //Byte values can be written as signed or unsigned
+    fn byte(&self, token: &Token) -> Result<u8, AssemblyError> {
+        Ok(self.ranged_value(token, -128, 255)? as u8)
+    }
+
+    fn nibble(&self, token: &Token) -> Result<u16, AssemblyError> {
+        Ok(self.ranged_value(token, 0, 15)? as u16)
+    }
+
+    fn register(&mut self) -> Result<u16, AssemblyError> {
+        let token = self.next()?;
+        self.register_of(&token).ok_or_else(|| token.error(format!("Expected a register but found '{}'", token.text)))
+    }
+
+    fn register_of(&self, token: &Token) -> Option<u16> {
+        parse_register(&token.text).or_else(|| self.aliases.get(&token.text).copied()).map(|reg| reg as u16)
+    }
+
+    fn operand(&mut self) -> Result<(Operand, Token), AssemblyError> {
+        let token = self.next()?;
+        match self.register_of(&token) {
+            Some(reg) => Ok((Operand::Register(reg as u8), token)),
+            None => Ok((Operand::Number(self.byte(&token)? as i64), token))
+        }
+    }
+
+    fn statement(&mut self) -> Result<(), AssemblyError> {
+        let token = self.next()?;
+
+        if let Some(macro_def) = self.macros.get(&token.text).cloned() {
+            return self.expand(&token, macro_def);
+        }
+        if self.register_of(&token).is_some() {
+            return self.register_statement(token);
+        }
+        if parse_number(&token.text).is_some() || self.constants.contains_key(&token.text) {
+            let byte = self.byte(&token)?;
+            return self.emit_byte(byte, &token);
+        }
+
+        match token.text.as_str() {
+            ":" => {
+                let name = self.name()?;
+                if self.labels.contains_key(&name.text) {
+                    return Err(name.error(format!("The label '{}' is already defined", name.text)));
+                }
+                self.labels.insert(name.text, self.address as u16);
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+                self.aliases.insert(name.text, reg as u8);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value_token = self.next()?;
+                let value = self.known_value(&value_token)?;
+                self.constants.insert(name.text, value);
+            }
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+                while self.peek() != Some("{") {
+                    args.push(self.name()?.text);
+                }
+                self.expect("{")?;
+                let mut body = Vec::new();
+                let mut depth = 1;
+                loop {
+                    let body_token = self.next()?;
+                    match body_token.text.as_str() {
+                        "{" => depth += 1,
+                        "}" => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    body.push(body_token);
+                }
+                self.macros.insert(name.text, Macro { args, body });
+            }
+            ":byte" => {
+                let value = self.next()?;
+                let byte = self.byte(&value)?;
+                self.emit_byte(byte, &value)?;
+            }
+            ":org" => {
+                let value = self.next()?;
+                self.address = self.ranged_value(&value, PROGRAM_START as i64, MEMORY_SIZE as i64 - 1)? as usize;
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.emit_address(0x2000, &target)?;
+            }
+            ":unpack" => {
+                //Loads V0 with a 4 bit tag and the high bits of an address, and V1 with the low bits of the address
+                let tag_token = self.next()?;
+                let tag = self.nibble(&tag_token)?;
+                let target = self.next()?;
+                match self.value(&target)? {
+                    Some(address) => {
+                        self.emit(0x6000 | (tag << 4) | ((address as u16 >> 8) & 0xF), &target)?;
+                        self.emit(0x6100 | (address as u16 & 0xFF), &target)?;
+                    }
+                    None => {
+                        self.fixups.push(Fixup { address: self.address + 1, kind: FixupKind::UnpackHigh, token: target.clone() });
+                        self.emit(0x6000 | (tag << 4), &target)?;
+                        self.fixups.push(Fixup { address: self.address + 1, kind: FixupKind::UnpackLow, token: target.clone() });
+                        self.emit(0x6100, &target)?;
+                    }
+                }
+            }
+            ":breakpoint" => {
+                //Breakpoints are set in the debugger instead, the name is ignored
+                self.next()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            "clear" => self.emit(0x00E0, &token)?,
+            "return" | ";" => self.emit(0x00EE, &token)?,
+            "hires" => self.emit(0x00FF, &token)?,
+            "lores" => self.emit(0x00FE, &token)?,
+            "exit" => self.emit(0x00FD, &token)?,
+            "scroll-left" => self.emit(0x00FC, &token)?,
+            "scroll-right" => self.emit(0x00FB, &token)?,
+            "audio" => self.emit(0xF002, &token)?,
+            "scroll-down" | "scroll-up" | "plane" => {
+                let value = self.next()?;
+                let n = self.nibble(&value)?;
+                let opcode = match token.text.as_str() {
+                    "scroll-down" => 0x00C0 | n,
+                    "scroll-up" => 0x00D0 | n,
+                    _ => 0xF001 | (n << 8)
+                };
+                self.emit(opcode, &token)?;
+            }
+            "jump" | "jump0" | "native" => {
+                let target = self.next()?;
+                let opcode = match token.text.as_str() {
+                    "jump" => 0x1000,
+                    "jump0" => 0xB000,
+                    _ => 0x0000
+                };
+                self.emit_address(opcode, &target)?;
+            }
+            "bcd" | "saveflags" | "loadflags" => {
+                let reg = self.register()?;
+                let opcode = match token.text.as_str() {
+                    "bcd" => 0xF033,
+                    "saveflags" => 0xF075,
+                    _ => 0xF085
+                };
+                self.emit(opcode | (reg << 8), &token)?;
+            }
+            "save" | "load" => {
+                let reg_x = self.register()?;
+                if self.peek() == Some("-") {
+                    self.next()?;
+                    let reg_y = self.register()?;
+                    let opcode = if token.text == "save" { 0x5002 } else { 0x5003 };
+                    self.emit(opcode | (reg_x << 8) | (reg_y << 4), &token)?;
+                } else {
+                    let opcode = if token.text == "save" { 0xF055 } else { 0xF065 };
+                    self.emit(opcode | (reg_x << 8), &token)?;
+                }
+            }
+            "sprite" => {
+                let reg_x = self.register()?;
+                let reg_y = self.register()?;
+                let height = self.next()?;
+                let n = self.nibble(&height)?;
+                self.emit(0xD000 | (reg_x << 8) | (reg_y << 4) | n, &token)?;
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let reg = self.register()?;
+                let opcode = match token.text.as_str() {
+                    "delay" => 0xF015,
+                    "buzzer" => 0xF018,
+                    _ => 0xF03A
+                };
+                self.emit(opcode | (reg << 8), &token)?;
+            }
+            "i" => self.i_statement()?,
+            "if" => self.if_statement(&token)?,
+            "else" => {
+                let branch = self.branches.last_mut().ok_or_else(|| token.error("'else' without 'if ... begin'".to_string()))?;
+                let if_jump = branch.jump;
+                branch.jump = self.address;
+                self.emit(0x1000, &token)?;
+                self.patch_jump(if_jump, self.address, &token)?;
+            }
+            "end" => {
+                let branch = self.branches.pop().ok_or_else(|| token.error("'end' without 'if ... begin'".to_string()))?;
+                self.patch_jump(branch.jump, self.address, &token)?;
+            }
+            "loop" => {
+                //'again' jumps back to the start of the loop, so it has to be in reach of a 12 bit jump
+                if self.address > 0xFFF {
+                    return Err(token.error(format!("Loop start {:#X} does not fit in 12 bits", self.address)));
+                }
+                self.loops.push(Loop { start: self.address as u16, exits: Vec::new(), token });
+            }
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err(token.error("'while' outside of 'loop ... again'".to_string()));
+                }
+                let condition = self.condition()?;
+                for opcode in condition.prelude {
+                    self.emit(opcode, &token)?;
+                }
+                self.emit(condition.skip_if_true, &token)?;
+                let exit = self.address;
+                self.emit(0x1000, &token)?;
+                self.loops.last_mut().unwrap().exits.push(exit);
+            }
+            "again" => {
+                let loop_def = self.loops.pop().ok_or_else(|| token.error("'again' without 'loop'".to_string()))?;
+                self.emit(0x1000 | loop_def.start, &token)?;
+                for exit in loop_def.exits {
+                    self.patch_jump(exit, self.address, &token)?;
+                }
+            }
+            _ if token.text.starts_with(':') => {
+                return Err(token.error(format!("Unknown directive '{}'", token.text)));
+            }
+            _ if is_reserved(&token.text) => {
+                return Err(token.error(format!("Unexpected '{}'", token.text)));
+            }
+            _ => {
+                //Any other name is a call to a subroutine label
+                self.emit_address(0x2000, &token)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expand(&mut self, token: &Token, macro_def: Macro) -> Result<(), AssemblyError> {
+        if token.depth >= MAX_MACRO_DEPTH {
+            return Err(token.error(format!("Macro '{}' expands too deeply", token.text)));
+        }
+        let mut args = HashMap::new();
+        for arg in &macro_def.args {
+            args.insert(arg.clone(), self.next()?.text);
+        }
+        for body_token in macro_def.body.iter().rev() {
+            let mut expanded = body_token.clone();
+            if let Some(value) = args.get(&expanded.text) {
+                expanded.text = value.clone();
+            }
+            expanded.depth = token.depth + 1;
+            self.tokens.push_front(expanded);
+        }
+        Ok(())
+    }
+
+    fn register_statement(&mut self, token: Token) -> Result<(), AssemblyError> {
+        let reg_x = self.register_of(&token).unwrap();
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => {
+                let source = self.next()?;
+                match source.text.as_str() {
+                    "random" => {
+                        let mask = self.next()?;
+                        let byte = self.byte(&mask)? as u16;
+                        self.emit(0xC000 | (reg_x << 8) | byte, &op)?;
+                    }
+                    "key" => self.emit(0xF00A | (reg_x << 8), &op)?,
+                    "delay" => self.emit(0xF007 | (reg_x << 8), &op)?,
+                    _ => match self.register_of(&source) {
+                        Some(reg_y) => self.emit(0x8000 | (reg_x << 8) | (reg_y << 4), &op)?,
+                        None => {
+                            let byte = self.byte(&source)? as u16;
+                            self.emit(0x6000 | (reg_x << 8) | byte, &op)?;
+                        }
+                    }
+                }
+            }
+            "+=" | "-=" => {
+                let (operand, _) = self.operand()?;
+                match (operand, op.text.as_str()) {
+                    (Operand::Register(reg_y), "+=") => self.emit(0x8004 | (reg_x << 8) | ((reg_y as u16) << 4), &op)?,
+                    (Operand::Register(reg_y), _) => self.emit(0x8005 | (reg_x << 8) | ((reg_y as u16) << 4), &op)?,
+                    (Operand::Number(n), "+=") => self.emit(0x7000 | (reg_x << 8) | (n as u16 & 0xFF), &op)?,
+                    (Operand::Number(n), _) => {
+                        //Subtracting a constant adds its negation
+                        self.emit(0x7000 | (reg_x << 8) | ((n as u8).wrapping_neg() as u16), &op)?;
+                    }
+                }
+            }
+            "=-" | "|=" | "&=" | "^=" | ">>=" | "<<=" => {
+                let reg_y = self.register()?;
+                let n = match op.text.as_str() {
+                    "|=" => 0x1,
+                    "&=" => 0x2,
+                    "^=" => 0x3,
+                    ">>=" => 0x6,
+                    "=-" => 0x7,
+                    _ => 0xE
+                };
+                self.emit(0x8000 | (reg_x << 8) | (reg_y << 4) | n, &op)?;
+            }
+            _ => return Err(op.error(format!("Expected an assignment operator but found '{}'", op.text)))
+        }
+        Ok(())
+    }
+
+    fn i_statement(&mut self) -> Result<(), AssemblyError> {
+        let op = self.next()?;
+        match op.text.as_str() {
+            ":=" => {
+                let source = self.next()?;
+                match source.text.as_str() {
+                    "hex" => {
+                        let reg = self.register()?;
+                        self.emit(0xF029 | (reg << 8), &op)?;
+                    }
+                    "bighex" => {
+                        let reg = self.register()?;
+                        self.emit(0xF030 | (reg << 8), &op)?;
+                    }
+                    "long" => {
+                        let target = self.next()?;
+                        self.emit(0xF000, &op)?;
+                        match self.value(&target)? {
+                            Some(address) => {
+                                if !(0..MEMORY_SIZE as i64).contains(&address) {
+                                    return Err(target.error(format!("Address {:#X} does not fit in 16 bits", address)));
+                                }
+                                self.emit(address as u16, &target)?;
+                            }
+                            None => {
+                                self.fixups.push(Fixup { address: self.address, kind: FixupKind::LongAddress, token: target.clone() });
+                                self.emit(0, &target)?;
+                            }
+                        }
+                    }
+                    _ => self.emit_address(0xA000, &source)?
+                }
+            }
+            "+=" => {
+                let reg = self.register()?;
+                self.emit(0xF01E | (reg << 8), &op)?;
+            }
+            _ => return Err(op.error(format!("Expected ':=' or '+=' after i but found '{}'", op.text)))
+        }
+        Ok(())
+    }
+
+    fn if_statement(&mut self, token: &Token) -> Result<(), AssemblyError> {
+        let condition = self.condition()?;
+        for opcode in &condition.prelude {
+            self.emit(*opcode, token)?;
+        }
+        let keyword = self.next()?;
+        match keyword.text.as_str() {
+            "then" => {
+                //The next statement is skipped when the condition is false
+                self.emit(condition.skip_if_false, token)?;
+                self.statement()
+            }
+            "begin" => {
+                //The jump over the block is skipped when the condition is true
+                self.emit(condition.skip_if_true, token)?;
+                self.branches.push(Branch { jump: self.address, token: token.clone() });
+                self.emit(0x1000, token)
+            }
+            _ => Err(keyword.error(format!("Expected 'then' or 'begin' but found '{}'", keyword.text)))
+        }
+    }
+
+    fn condition(&mut self) -> Result<Condition, AssemblyError> {
+        let reg_x = self.register()?;
+        let op = self.next()?;
+        match op.text.as_str() {
+            "key" => return Ok(Condition { prelude: vec![], skip_if_true: 0xE09E | (reg_x << 8), skip_if_false: 0xE0A1 | (reg_x << 8) }),
+            "-key" => return Ok(Condition { prelude: vec![], skip_if_true: 0xE0A1 | (reg_x << 8), skip_if_false: 0xE09E | (reg_x << 8) }),
+            _ => {}
+        }
+
+        let (operand, _) = self.operand()?;
+        let (equal, not_equal) = match operand {
+            Operand::Register(reg_y) => (0x5000 | (reg_x << 8) | ((reg_y as u16) << 4), 0x9000 | (reg_x << 8) | ((reg_y as u16) << 4)),
+            Operand::Number(n) => (0x3000 | (reg_x << 8) | (n as u16 & 0xFF), 0x4000 | (reg_x << 8) | (n as u16 & 0xFF))
+        };
+        match op.text.as_str() {
+            "==" => Ok(Condition { prelude: vec![], skip_if_true: equal, skip_if_false: not_equal }),
+            "!=" => Ok(Condition { prelude: vec![], skip_if_true: not_equal, skip_if_false: equal }),
+            "<" | ">=" | ">" | "<=" => {
+                /*Comparisons subtract the operands into VF so that VF is the no borrow flag. VX < Y is VX - Y
+                borrowing, and VX > Y is Y - VX borrowing*/
+                let x = Operand::Register(reg_x as u8);
+                let prelude = if op.text == "<" || op.text == ">=" {
+                    subtract_into_vf(x, operand)
+                } else {
+                    subtract_into_vf(operand, x)
+                };
+                let vf_flag = if op.text == "<" || op.text == ">" { 0 } else { 1 };
+                Ok(Condition { prelude, skip_if_true: 0x3F00 | vf_flag, skip_if_false: 0x4F00 | vf_flag })
+            }
+            _ => Err(op.error(format!("Expected a comparison but found '{}'", op.text)))
+        }
+    }
+
+    fn patch_jump(&mut self, jump: usize, target: usize, token: &Token) -> Result<(), AssemblyError> {
+        if target > 0xFFF {
+            return Err(token.error(format!("Jump target {:#X} does not fit in 12 bits", target)));
+        }
+        self.memory[jump] = 0x10 | (target >> 8) as u8;
+        self.memory[jump+1] = target as u8;
+        Ok(())
+    }
+
+    //Resolves the labels used before they were defined and returns the assembled ROM
+    fn finish(mut self) -> Result<Vec<u8>, AssemblyError> {
+        if let Some(loop_def) = self.loops.last() {
+            return Err(loop_def.token.error("'loop' without 'again'".to_string()));
+        }
+        if let Some(branch) = self.branches.last() {
+            return Err(branch.token.error("'if ... begin' without 'end'".to_string()));
+        }
+
+        for fixup in &self.fixups {
+            let address = match self.labels.get(&fixup.token.text) {
+                Some(address) => *address,
+                None => return Err(fixup.token.error(format!("Undefined name '{}'", fixup.token.text)))
+            };
+            match fixup.kind {
+                FixupKind::Address => {
+                    if address > 0xFFF {
+                        return Err(fixup.token.error(format!("Address {:#X} does not fit in 12 bits", address)));
+                    }
+                    self.memory[fixup.address] |= (address >> 8) as u8;
+                    self.memory[fixup.address+1] = address as u8;
+                }
+                FixupKind::LongAddress => {
+                    self.memory[fixup.address] = (address >> 8) as u8;
+                    self.memory[fixup.address+1] = address as u8;
+                }
+                FixupKind::UnpackHigh => self.memory[fixup.address] |= ((address >> 8) & 0xF) as u8,
+                FixupKind::UnpackLow => self.memory[fixup.address] = address as u8
+            }
+        }
+
+        Ok(self.memory[PROGRAM_START..self.end].to_vec())
+    }
+}
+
+//Instructions that leave VF as 1 if A - B does not borrow, using VF as scratch for constants
+fn subtract_into_vf(a: Operand, b: Operand) -> Vec<u16> {
+    match (a, b) {
+        (Operand::Register(reg_a), Operand::Register(reg_b)) => vec![0x8F00 | ((reg_a as u16) << 4), 0x8F05 | ((reg_b as u16) << 4)],
+        (Operand::Register(reg_a), Operand::Number(n)) => vec![0x6F00 | (n as u16 & 0xFF), 0x8F07 | ((reg_a as u16) << 4)],
+        (Operand::Number(n), Operand::Register(reg_b)) => vec![0x6F00 | (n as u16 & 0xFF), 0x8F05 | ((reg_b as u16) << 4)],
+        (Operand::Number(a), Operand::Number(b)) => vec![0x6F00 | ((a >= b) as u16)]
+    }
+}
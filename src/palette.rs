@@ -0,0 +1,92 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use crossterm::style::Color;
+
+use chip8::filter;
+
+//Colours of the pixels for each combination of the XO-CHIP display planes: none, plane 1, plane 2 and both planes
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Palette {
+    pub colors: [Color; 4]
+}
+
+pub const THEMES: [&str; 5] = ["default", "green", "amber", "octo", "paper"];
+
+impl Palette {
+    pub fn theme(name: &str) -> Option<Palette> {
+        let colors = match name {
+            //The terminal's own colours, with the second plane in yellow and both planes in red
+            "default" => [Color::Reset, Color::Reset, Color::DarkYellow, Color::DarkRed],
+            //Green phosphor monitor
+            "green" => [rgb(0x0A1A0A), rgb(0x33FF66), rgb(0x1A8033), rgb(0xB3FFCC)],
+            //Amber phosphor monitor
+            "amber" => [rgb(0x1A0F00), rgb(0xFFB000), rgb(0x8C5A00), rgb(0xFFE0A0)],
+            //The default colours of the Octo IDE
+            "octo" => [rgb(0x996600), rgb(0xFFCC00), rgb(0xFF6600), rgb(0x662200)],
+            //Black on white
+            "paper" => [rgb(0xFFFFFF), rgb(0x000000), rgb(0x808080), rgb(0x404040)],
+            _ => return None
+        };
+        Some(Palette { colors })
+    }
+
+    //Replaces the colour of a plane combination, 0 is the background and 1 is the foreground
+    pub fn set(&mut self, planes: usize, color: Color) {
+        self.colors[planes & 0x3] = color;
+    }
+
+    /*Colour of a pixel, pixels faded by the decay filter are blended towards the background colour. Colours that are
+    not RGB can not be blended so faded pixels are shown in grey, and the terminal's background is taken to be black*/
+    pub fn color(&self, pixel: u8) -> Color {
+        let color = self.colors[filter::planes(pixel) as usize];
+        let fade = filter::fade(pixel) as u32;
+        if fade == 0 {
+            return color;
+        }
+        let background = match self.colors[0] {
+            Color::Reset => Color::Rgb { r: 0, g: 0, b: 0 },
+            background => background
+        };
+        match (color, background) {
+            (Color::Rgb { r, g, b }, Color::Rgb { r: br, g: bg, b: bb }) => {
+                let levels = filter::DECAY_LEVELS as u32 + 1;
+                let blend = |from: u8, to: u8| ((from as u32 * (levels - fade) + to as u32 * fade) / levels) as u8;
+                Color::Rgb { r: blend(r, br), g: blend(g, bg), b: blend(b, bb) }
+            }
+            _ if fade == 1 => Color::Grey,
+            _ => Color::DarkGrey
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette::theme("default").unwrap()
+    }
+}
+
+fn rgb(color: u32) -> Color {
+    Color::Rgb { r: (color >> 16) as u8, g: (color >> 8) as u8, b: color as u8 }
+}
+
+//Colours are 24-bit RGB in hex such as #FFB000 or FFB000, or default for the terminal's own colour
+pub fn parse_color(text: &str) -> Option<Color> {
+    if text == "default" {
+        return Some(Color::Reset);
+    }
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(hex, 16).ok().map(rgb)
+}
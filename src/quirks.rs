@@ -0,0 +1,100 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*Behaviours of ambiguous instructions that differ between the CHIP-8 interpreters programs were written for.
+Each flag is true when the instruction behaves the way named by the flag*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Quirks {
+    pub vf_reset: bool,     //8XY1, 8XY2 and 8XY3 reset VF to 0
+    pub memory: bool,       //FX55 and FX65 increment I past the last register stored or loaded
+    pub shifting: bool,     //8XY6 and 8XYE shift VX in place instead of storing VY shifted into VX
+    pub jumping: bool,      //BXNN jumps to XNN plus VX instead of NNN plus V0
+    pub clipping: bool,     //Sprites are clipped at the edges of the display instead of wrapping around
+    pub display_wait: bool  //Drawing a sprite waits for the next frame before the CPU continues
+}
+
+pub const PROFILES: [&str; 5] = ["classic", "vip", "chip48", "schip", "xochip"];
+pub const FLAGS: [&str; 6] = ["vf_reset", "memory", "shifting", "jumping", "clipping", "display_wait"];
+
+impl Quirks {
+    //Returns the quirks of a named interpreter profile
+    pub fn profile(name: &str) -> Option<Quirks> {
+        match name {
+            //The behaviour of this emulator before quirks could be chosen, which most CHIP-8 ROMs expect
+            "classic" => Some(Quirks {
+                vf_reset: false,
+                memory: false,
+                shifting: true,
+                jumping: false,
+                clipping: false,
+                display_wait: false
+            }),
+            //The original COSMAC VIP interpreter
+            "vip" => Some(Quirks {
+                vf_reset: true,
+                memory: true,
+                shifting: false,
+                jumping: false,
+                clipping: true,
+                display_wait: true
+            }),
+            //CHIP-48 on the HP-48 calculators
+            "chip48" => Some(Quirks {
+                vf_reset: false,
+                memory: true,
+                shifting: true,
+                jumping: true,
+                clipping: true,
+                display_wait: false
+            }),
+            //SUPER-CHIP 1.1
+            "schip" => Some(Quirks {
+                vf_reset: false,
+                memory: false,
+                shifting: true,
+                jumping: true,
+                clipping: true,
+                display_wait: false
+            }),
+            //XO-CHIP as implemented by Octo
+            "xochip" => Some(Quirks {
+                vf_reset: false,
+                memory: true,
+                shifting: false,
+                jumping: false,
+                clipping: false,
+                display_wait: false
+            }),
+            _ => None
+        }
+    }
+
+    //Overrides a single quirk by name, returns false if the name is not a quirk
+    pub fn set(&mut self, flag: &str, value: bool) -> bool {
+        match flag {
+            "vf_reset" => self.vf_reset = value,
+            "memory" => self.memory = value,
+            "shifting" => self.shifting = value,
+            "jumping" => self.jumping = value,
+            "clipping" => self.clipping = value,
+            "display_wait" => self.display_wait = value,
+            _ => return false
+        }
+        true
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::profile("classic").unwrap()
+    }
+}
@@ -0,0 +1,127 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use chip8::filter;
+
+/*Packs the pixels of the framebuffer into terminal cells. Terminal cells are about twice as tall as they are wide,
+so one pixel per cell stretches the screen to twice its height*/
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RenderMode {
+    //One pixel per cell drawn as '#'
+    Ascii,
+    //Two rows of pixels per cell drawn with the half block characters
+    HalfBlock,
+    //2x4 pixels per cell drawn with the braille characters
+    Braille
+}
+
+pub const RENDER_MODES: [&str; 3] = ["ascii", "halfblock", "braille"];
+
+/*A terminal cell, with the pixels shown in the foreground and background. Pixels are the XO-CHIP planes, with how far
+they have faded when the framebuffer went through the decay filter*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Cell {
+    pub symbol: char,
+    pub foreground: u8,
+    pub background: u8
+}
+
+const BLANK: Cell = Cell { symbol: ' ', foreground: 0, background: 0 };
+
+//Bit of each pixel of a braille cell by row and column, the dots are not numbered in reading order
+const BRAILLE_DOTS: [[u32; 2]; 4] = [
+    [0x01, 0x08],
+    [0x02, 0x10],
+    [0x04, 0x20],
+    [0x40, 0x80]
+];
+
+impl RenderMode {
+    pub fn parse(name: &str) -> Option<RenderMode> {
+        match name {
+            "ascii" => Some(RenderMode::Ascii),
+            "halfblock" => Some(RenderMode::HalfBlock),
+            "braille" => Some(RenderMode::Braille),
+            _ => None
+        }
+    }
+
+    //Width and height of the pixels packed into one cell
+    fn cell_pixels(self) -> (usize, usize) {
+        match self {
+            RenderMode::Ascii => (1, 1),
+            RenderMode::HalfBlock => (1, 2),
+            RenderMode::Braille => (2, 4)
+        }
+    }
+
+    //Number of columns and rows of cells needed for a resolution
+    pub fn screen_size(self, width: usize, height: usize) -> (usize, usize) {
+        let (cell_width, cell_height) = self.cell_pixels();
+        (width.div_ceil(cell_width), height.div_ceil(cell_height))
+    }
+
+    //Cells of the framebuffer in rows from the top left, with each pixel scaled up to a square of scale pixels
+    pub fn render(self, gfx: &[u8], width: usize, height: usize, scale: usize) -> Vec<Cell> {
+        let (columns, rows) = self.screen_size(width*scale, height*scale);
+        let (cell_width, cell_height) = self.cell_pixels();
+        let pixel = |x: usize, y: usize| {
+            if x < width*scale && y < height*scale { gfx[(y/scale)*width + x/scale] } else { 0 }
+        };
+        let mut cells = Vec::with_capacity(columns*rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let (x, y) = (column*cell_width, row*cell_height);
+                let cell = match self {
+                    RenderMode::Ascii => match pixel(x, y) {
+                        0 => BLANK,
+                        planes => Cell { symbol: '#', foreground: planes, background: 0 }
+                    },
+                    /*A cell with different planes in each half shows one half in the background colour. Plane 1 is drawn in
+                    the terminal's default colour by the default theme, which can only be a foreground colour, so it is
+                    kept in the foreground*/
+                    RenderMode::HalfBlock => match (pixel(x, y), pixel(x, y+1)) {
+                        (0, 0) => BLANK,
+                        (top, 0) => Cell { symbol: '\u{2580}', foreground: top, background: 0 },
+                        (0, bottom) => Cell { symbol: '\u{2584}', foreground: bottom, background: 0 },
+                        (top, bottom) if top == bottom => Cell { symbol: '\u{2588}', foreground: top, background: 0 },
+                        (top, 1) => Cell { symbol: '\u{2584}', foreground: 1, background: top },
+                        (top, bottom) => Cell { symbol: '\u{2580}', foreground: top, background: bottom }
+                    },
+                    //Braille dots have a single colour, the planes of all the lit pixels in the cell faded like the brightest
+                    RenderMode::Braille => {
+                        let mut dots = 0;
+                        let mut planes = 0;
+                        let mut fade = filter::DECAY_LEVELS;
+                        for (dy, row_dots) in BRAILLE_DOTS.iter().enumerate() {
+                            for (dx, dot) in row_dots.iter().enumerate() {
+                                let lit = pixel(x + dx, y + dy);
+                                if lit != 0 {
+                                    dots |= dot;
+                                    planes |= filter::planes(lit);
+                                    fade = fade.min(filter::fade(lit));
+                                }
+                            }
+                        }
+                        match std::char::from_u32(0x2800 + dots) {
+                            Some(symbol) if dots != 0 => Cell { symbol, foreground: planes | fade << 2, background: 0 },
+                            _ => BLANK
+                        }
+                    }
+                };
+                cells.push(cell);
+            }
+        }
+        cells
+    }
+}
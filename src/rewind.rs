@@ -0,0 +1,60 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::VecDeque;
+
+use crate::chip8::{Chip8, MEMORY_SIZE};
+
+//Upper bound on the memory used by rewind snapshots, about 15 seconds of frames
+const REWIND_MEMORY: usize = 64*1024*1024;
+
+//Snapshots are taken before every emulated frame, and before every instruction in step mode
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SnapshotKind {
+    Frame,
+    Instruction
+}
+
+//Ring buffer of machine snapshots, the oldest snapshot is dropped when the buffer is full
+pub struct Rewind {
+    snapshots: VecDeque<(Chip8, SnapshotKind)>,
+    capacity: usize
+}
+
+impl Rewind {
+    pub fn new() -> Rewind {
+        //The 64 KiB of memory dominates the size of each snapshot
+        let capacity = REWIND_MEMORY/MEMORY_SIZE;
+        Rewind {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity
+        }
+    }
+
+    pub fn push(&mut self, emu: &Chip8, kind: SnapshotKind) {
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back((emu.clone(), kind));
+    }
+
+    //Takes the most recent snapshot, None once the buffer has been rewound all the way
+    pub fn pop(&mut self) -> Option<(Chip8, SnapshotKind)> {
+        self.snapshots.pop_back()
+    }
+}
+
+impl Default for Rewind {
+    fn default() -> Rewind {
+        Rewind::new()
+    }
+}
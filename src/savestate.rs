@@ -0,0 +1,139 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::chip8::Chip8;
+
+/*Save state files start with a magic number, the format version and a hash of the ROM they were taken with, followed
+by the machine state written by Chip8::write_state*/
+const MAGIC: [u8; 4] = *b"C8ST";
+const HEADER_LEN: usize = 14;
+
+//Bumped whenever the layout of the machine state changes, older save states are rejected rather than misread
+pub const VERSION: u16 = 1;
+
+//Save states are kept in numbered slots
+pub const SLOTS: u8 = 10;
+
+#[derive(Debug)]
+pub enum SaveStateError {
+    Io(io::Error),
+    NotASaveState,
+    IncompatibleVersion { version: u16 },
+    DifferentRom,
+    Corrupt
+}
+
+impl fmt::Display for SaveStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveStateError::Io(error) => write!(f, "{}", error),
+            SaveStateError::NotASaveState => write!(f, "not a save state file"),
+            SaveStateError::IncompatibleVersion { version } =>
+                write!(f, "save state format version {} is not supported (expected {})", version, VERSION),
+            SaveStateError::DifferentRom => write!(f, "save state was taken with a different ROM"),
+            SaveStateError::Corrupt => write!(f, "save state is corrupt")
+        }
+    }
+}
+
+impl Error for SaveStateError {}
+
+impl From<io::Error> for SaveStateError {
+    fn from(error: io::Error) -> SaveStateError {
+        SaveStateError::Io(error)
+    }
+}
+
+//64 bit FNV-1a hash, identifies the ROM a save state belongs to
+pub fn hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xCBF2_9CE4_8422_2325;
+    for &byte in data {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+pub fn encode(emu: &Chip8, rom_hash: u64) -> Vec<u8> {
+    let mut state = Vec::new();
+    state.extend_from_slice(&MAGIC);
+    state.extend_from_slice(&VERSION.to_be_bytes());
+    state.extend_from_slice(&rom_hash.to_be_bytes());
+    emu.write_state(&mut state);
+    state
+}
+
+//Restores the machine from a save state, the machine is left unchanged if the save state is rejected
+pub fn decode(emu: &mut Chip8, state: &[u8], rom_hash: u64) -> Result<(), SaveStateError> {
+    if state.len() < HEADER_LEN || state[..4] != MAGIC {
+        return Err(SaveStateError::NotASaveState);
+    }
+    let mut reader = StateReader::new(&state[4..HEADER_LEN]);
+    let version = reader.u16().ok_or(SaveStateError::Corrupt)?;
+    if version != VERSION {
+        return Err(SaveStateError::IncompatibleVersion { version });
+    }
+    let hash = reader.u64().ok_or(SaveStateError::Corrupt)?;
+    if hash != rom_hash {
+        return Err(SaveStateError::DifferentRom);
+    }
+
+    let mut restored = emu.clone();
+    let mut reader = StateReader::new(&state[HEADER_LEN..]);
+    if restored.read_state(&mut reader).is_none() || !reader.is_empty() {
+        return Err(SaveStateError::Corrupt);
+    }
+    *emu = restored;
+    Ok(())
+}
+
+//Reads big endian values from a save state, each read returns None if the data ends early
+pub struct StateReader<'a> {
+    data: &'a [u8]
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> StateReader<'a> {
+        StateReader { data }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.data.len() {
+            return None;
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(bytes)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.bytes(1).map(|bytes| bytes[0])
+    }
+
+    pub fn u16(&mut self) -> Option<u16> {
+        self.bytes(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn u64(&mut self) -> Option<u64> {
+        let mut value = [0; 8];
+        value.copy_from_slice(self.bytes(8)?);
+        Some(u64::from_be_bytes(value))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+}
@@ -0,0 +1,110 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::chip8::Chip8;
+use crate::debugger::OpcodePattern;
+use crate::disasm;
+
+//Binary traces start with a magic number and format version, followed by fixed size records
+const MAGIC: [u8; 4] = *b"C8TR";
+const VERSION: u8 = 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TraceFormat {
+    //One line per instruction with the disassembly, registers, I and timers
+    Text,
+    /*24 bytes per instruction, all big endian: PC (2), opcode (2), V0-VF (16), I (2), delay timer (1) and sound
+    timer (1)*/
+    Binary
+}
+
+impl TraceFormat {
+    pub fn parse(name: &str) -> Option<TraceFormat> {
+        match name {
+            "text" => Some(TraceFormat::Text),
+            "binary" => Some(TraceFormat::Binary),
+            _ => None
+        }
+    }
+}
+
+/*Logs the state of the machine before each executed instruction. Only instructions in the address range and matching
+one of the opcode patterns, if any are given, are logged*/
+pub struct Trace {
+    writer: BufWriter<File>,
+    format: TraceFormat,
+    range: Option<(usize, usize)>,
+    patterns: Vec<OpcodePattern>,
+    //Tracing stops at the first write error, which is reported when the trace is finished
+    error: Option<io::Error>
+}
+
+impl Trace {
+    pub fn create(path: &str, format: TraceFormat, range: Option<(usize, usize)>, patterns: Vec<OpcodePattern>) -> io::Result<Trace> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        if format == TraceFormat::Binary {
+            writer.write_all(&MAGIC)?;
+            writer.write_all(&[VERSION])?;
+        }
+        Ok(Trace { writer, format, range, patterns, error: None })
+    }
+
+    pub fn record(&mut self, emu: &Chip8) {
+        if self.error.is_some() {
+            return;
+        }
+        let pc = emu.pc() as usize;
+        let opcode = emu.opcode_at(pc);
+        if let Some((start, end)) = self.range {
+            if pc < start || pc > end {
+                return;
+            }
+        }
+        if !self.patterns.is_empty() && !self.patterns.iter().any(|pattern| pattern.matches(opcode)) {
+            return;
+        }
+
+        let registers: Vec<u8> = (0..16).map(|reg| emu.register(reg)).collect();
+        let result = match self.format {
+            TraceFormat::Text => {
+                let (line, _) = disasm::instruction_line(emu.memory(), pc);
+                let registers: Vec<String> = registers.iter().map(|value| format!("{:02X}", value)).collect();
+                writeln!(self.writer, "{:<40} V: {} I: {:#06X} DT: {:#04X} ST: {:#04X}", line, registers.join(" "),
+                    emu.i(), emu.delay_timer(), emu.sound_timer())
+            }
+            TraceFormat::Binary => {
+                let mut record = Vec::with_capacity(24);
+                record.extend_from_slice(&(pc as u16).to_be_bytes());
+                record.extend_from_slice(&opcode.to_be_bytes());
+                record.extend_from_slice(&registers);
+                record.extend_from_slice(&emu.i().to_be_bytes());
+                record.push(emu.delay_timer());
+                record.push(emu.sound_timer());
+                self.writer.write_all(&record)
+            }
+        };
+        if let Err(error) = result {
+            self.error = Some(error);
+        }
+    }
+
+    //Flushes the trace, returning the error that stopped it if there was one
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.error.take() {
+            Some(error) => Err(error),
+            None => self.writer.flush()
+        }
+    }
+}
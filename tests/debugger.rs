@@ -0,0 +1,39 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//Breakpoints written on the command line and in the console
+
+use chip8::debugger::{Breakpoint, OpcodePattern};
+
+fn opcode(text: &str) -> Breakpoint {
+    Breakpoint::Opcode(OpcodePattern::parse(text).unwrap())
+}
+
+#[test]
+fn addresses_need_the_hex_prefix() {
+    assert_eq!(Breakpoint::parse("0x234"), Ok(Breakpoint::Pc(0x234)));
+    assert_eq!(Breakpoint::parse(" 0X2a0 "), Ok(Breakpoint::Pc(0x2A0)));
+    assert!(Breakpoint::parse("512").is_err());
+    assert!(Breakpoint::parse("0x").is_err());
+}
+
+#[test]
+fn four_characters_are_an_opcode_pattern() {
+    assert_eq!(Breakpoint::parse("1234"), Ok(opcode("1234")));
+    assert_eq!(Breakpoint::parse("6000"), Ok(opcode("6000")));
+    assert_eq!(Breakpoint::parse("00E0"), Ok(opcode("00E0")));
+    assert_eq!(Breakpoint::parse("dxyn"), Ok(opcode("DXYN")));
+    assert!(OpcodePattern::parse("1234").unwrap().matches(0x1234));
+    assert!(OpcodePattern::parse("DXYN").unwrap().matches(0xD125));
+    assert!(!OpcodePattern::parse("6000").unwrap().matches(0x6001));
+    assert!(Breakpoint::parse("DXYZ").is_err());
+}
@@ -0,0 +1,46 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//The display filters advance once for each frame they are given
+
+use chip8::filter::{DisplayFilter, FilterMode};
+
+#[test]
+fn blend_shows_the_last_two_frames() {
+    let mut filter = DisplayFilter::new(FilterMode::Blend);
+    filter.apply(&[1, 0, 0]);
+    assert_eq!(filter.apply(&[0, 1, 0]), &[1, 1, 0]);
+    assert_eq!(filter.apply(&[0, 0, 1]), &[0, 1, 1]);
+}
+
+#[test]
+fn shows_the_filtered_frame_until_the_framebuffer_changes() {
+    let mut filter = DisplayFilter::new(FilterMode::Blend);
+    filter.apply(&[1, 0]);
+    filter.apply(&[0, 1]);
+    //Drawing the same frame again, as when paused, keeps the blend
+    assert_eq!(filter.shown(&[0, 1]), &[1, 1]);
+    assert_eq!(filter.shown(&[0, 1]), &[1, 1]);
+    //A change between frames such as a single step is shown as it is
+    assert_eq!(filter.shown(&[1, 1]), &[1, 1]);
+    assert_eq!(filter.shown(&[0, 0]), &[0, 0]);
+}
+
+#[test]
+fn decay_fades_over_frames_not_draws() {
+    let mut filter = DisplayFilter::new(FilterMode::Decay);
+    filter.apply(&[1]);
+    let faded = filter.apply(&[0])[0];
+    assert_eq!(filter.shown(&[0]), &[faded]);
+    assert_eq!(filter.shown(&[0]), &[faded]);
+    assert_ne!(filter.apply(&[0])[0], faded);
+}
@@ -0,0 +1,139 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//Tests of run_frame with a front-end that records what it was given
+
+use chip8::frontend::run_frame;
+use chip8::headless::{self, ScriptedKey};
+use chip8::{octo, savestate, Audio, Chip8, Display, Input, Quirks, StepOutcome};
+
+#[derive(Default)]
+struct Recorder {
+    frames: u64,
+    draws: u64,
+    sounds: Vec<bool>,
+    //The key held from a frame onwards
+    key: Option<(u64, usize)>
+}
+
+impl Display for Recorder {
+    fn draw(&mut self, _gfx: &[u8], width: usize, height: usize) {
+        assert_eq!((width, height), (64, 32));
+        self.draws += 1;
+    }
+}
+
+impl Input for Recorder {
+    fn update_keypad(&mut self, keypad: &mut [u8; 16]) {
+        if let Some((frame, key)) = self.key {
+            keypad[key] = (self.frames >= frame) as u8;
+        }
+        self.frames += 1;
+    }
+}
+
+impl Audio for Recorder {
+    fn sound(&mut self, playing: bool, _pattern: [u8; 16], _pitch: u8) {
+        self.sounds.push(playing);
+    }
+}
+
+fn machine(source: &str, quirks: Quirks) -> Chip8 {
+    chip8::init(octo::assemble(source).unwrap_or_else(|error| panic!("{}", error)), quirks)
+}
+
+#[test]
+fn presents_every_frame_and_ticks_the_timers() {
+    let mut emu = machine("v0 := 2\nbuzzer := v0\nv0 := 10\ndelay := v0\nloop again", Quirks::default());
+    let mut recorder = Recorder::default();
+    let mut cycles_owed = 0.0;
+    for _ in 0..3 {
+        assert_eq!(run_frame(&mut emu, &mut recorder, &mut cycles_owed, 10.0), Ok(StepOutcome::Continue));
+    }
+    assert_eq!(recorder.draws, 3);
+    assert_eq!(recorder.sounds, [true, false, false]);
+    assert_eq!(emu.delay_timer(), 7);
+}
+
+#[test]
+fn reads_the_keypad_before_each_frame() {
+    let mut emu = machine("v0 := key\ni := hex v0\nsprite v1 v1 5\nloop again", Quirks::default());
+    let mut recorder = Recorder { key: Some((4, 0x7)), ..Recorder::default() };
+    let mut cycles_owed = 0.0;
+    let mut drawn_at = None;
+    for frame in 0..10 {
+        if run_frame(&mut emu, &mut recorder, &mut cycles_owed, 8.0) == Ok(StepOutcome::Draw) && drawn_at.is_none() {
+            drawn_at = Some(frame);
+        }
+    }
+    assert_eq!(emu.register(0), 0x7);
+    assert_eq!(drawn_at, Some(4));
+}
+
+#[test]
+fn display_wait_ends_the_frame_at_a_draw() {
+    let source = "loop\n  sprite v0 v0 1\n  v2 += 1\nagain";
+    let mut waiting = machine(source, Quirks::profile("vip").unwrap());
+    let mut free = machine(source, Quirks::profile("schip").unwrap());
+    let mut recorder = Recorder::default();
+    let (mut waiting_owed, mut free_owed) = (0.0, 0.0);
+    for _ in 0..5 {
+        run_frame(&mut waiting, &mut recorder, &mut waiting_owed, 100.0).unwrap();
+        run_frame(&mut free, &mut recorder, &mut free_owed, 100.0).unwrap();
+    }
+    //One draw per frame, and the add after the fifth draw has not run yet
+    assert_eq!(waiting.register(2), 4);
+    assert!(waiting_owed < 1.0);
+    assert_eq!(free.register(2), 167);
+}
+
+#[test]
+fn stops_at_exit_without_presenting() {
+    let mut emu = machine("clear\nexit", Quirks::default());
+    let mut recorder = Recorder::default();
+    assert_eq!(run_frame(&mut emu, &mut recorder, &mut 0.0, 10.0), Ok(StepOutcome::Exit));
+    assert_eq!(recorder.draws, 0);
+}
+
+#[test]
+fn returns_the_error_that_halted_the_cpu() {
+    let mut emu = machine("return", Quirks::default());
+    let mut recorder = Recorder::default();
+    assert!(run_frame(&mut emu, &mut recorder, &mut 0.0, 10.0).is_err());
+    assert!(emu.halted().is_some());
+}
+
+#[test]
+fn matches_a_headless_run() {
+    //Both run frames the same way, so the same keys at the same frames give the same machine
+    let source = "
+        : main
+            v0 := key
+            v1 += 3
+            i := hex v0
+            sprite v1 v2 5
+            delay := v1
+            jump main";
+    let quirks = Quirks::profile("vip").unwrap();
+    let mut emu = machine(source, quirks);
+    let mut recorder = Recorder { key: Some((10, 0xA)), ..Recorder::default() };
+    let mut cycles_owed = 0.0;
+    for _ in 0..30 {
+        run_frame(&mut emu, &mut recorder, &mut cycles_owed, 500.0/60.0).unwrap();
+    }
+
+    let mut headless_emu = machine(source, quirks);
+    let keys = [ScriptedKey { frame: 10, key: 0xA, pressed: true }];
+    let run = headless::run(&mut headless_emu, Some(30), None, 500.0/60.0, &keys, &mut None);
+    assert_eq!(run.frames, 30);
+    assert_eq!(savestate::encode(&headless_emu, 0), savestate::encode(&emu, 0));
+}
@@ -0,0 +1,310 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+/*Runs the test ROMs in tests/roms headlessly and compares the framebuffer with the golden images in tests/golden,
+which are either ASCII images (.txt) in the format printed by headless mode or plain PBM images (.pbm). Run the tests
+with UPDATE_GOLDEN=1 to write the golden images from the current output. The flags and quirks tests check registers
+and memory against values worked out from the instruction set instead.
+
+The suite tests run the binaries of the community CHIP-8 test suite from tests/roms/suite against images of their
+passing screens in tests/golden/suite, which come from a reference emulator and are never written by UPDATE_GOLDEN.
+They are ignored until those files are committed, see the README*/
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use chip8::headless::{self, HeadlessRun, ScriptedKey, StopReason};
+use chip8::quirks::PROFILES;
+use chip8::{octo, Chip8, Quirks};
+
+const CYCLES_PER_FRAME: f64 = 500.0/60.0;
+
+fn assemble(name: &str) -> Vec<u8> {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms").join(name);
+    let source = fs::read_to_string(&path).unwrap_or_else(|why| panic!("couldn't read {}: {}", path.display(), why));
+    octo::assemble(&source).unwrap_or_else(|error| panic!("{}:{}", path.display(), error))
+}
+
+fn run_rom(name: &str, quirks: Quirks, frames: u64, keys: &[ScriptedKey]) -> (Chip8, HeadlessRun) {
+    let rom = assemble(name);
+    let mut emu = chip8::init(rom, quirks);
+    let run = headless::run(&mut emu, Some(frames), None, CYCLES_PER_FRAME, keys, &mut None);
+    (emu, run)
+}
+
+//Runs a binary ROM of the test suite, which reads the platform to test from 0x1FF instead of asking for it
+fn run_suite_rom(name: &str, quirks: Quirks, platform: Option<u8>, frames: u64, keys: &[ScriptedKey]) -> Chip8 {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/roms/suite").join(name);
+    let rom = fs::read(&path).unwrap_or_else(|why| panic!("couldn't read {}: {}", path.display(), why));
+    let mut emu = chip8::init(rom, quirks);
+    if let Some(platform) = platform {
+        emu.poke(0x1FF, platform);
+    }
+    let run = headless::run(&mut emu, Some(frames), None, CYCLES_PER_FRAME, keys, &mut None);
+    assert_eq!(run.stop, StopReason::Finished, "{} stopped early", name);
+    emu
+}
+
+//Reads a golden image as rows of characters, PBM images use '#' for black and '.' for white pixels
+fn read_golden(path: &Path) -> Vec<String> {
+    let golden = fs::read_to_string(path).unwrap_or_else(|why| panic!("couldn't read {}: {}", path.display(), why));
+    if path.extension().is_some_and(|extension| extension == "pbm") {
+        let mut values = golden.lines().filter(|line| !line.starts_with('#')).flat_map(|line| line.split_whitespace());
+        assert_eq!(values.next(), Some("P1"), "{} is not a plain PBM image", path.display());
+        let width: usize = values.next().and_then(|width| width.parse().ok()).expect("PBM width");
+        let pixels: Vec<char> = values.skip(1).map(|pixel| if pixel == "1" { '#' } else { '.' }).collect();
+        pixels.chunks(width).map(|row| row.iter().collect()).collect()
+    } else {
+        golden.lines().map(|line| line.to_string()).collect()
+    }
+}
+
+//Panics with the rows that differ and a marker under each differing pixel
+fn assert_golden(emu: &Chip8, golden: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden").join(golden);
+    let pbm = golden.ends_with(".pbm");
+    if env::var_os("UPDATE_GOLDEN").is_some() && !golden.starts_with("suite/") {
+        let image = if pbm {
+            headless::framebuffer_pbm(&emu.gfx, emu.width(), emu.height())
+        } else {
+            headless::framebuffer_ascii(&emu.gfx, emu.width(), emu.height())
+        };
+        fs::write(&path, image).unwrap_or_else(|why| panic!("couldn't write to {}: {}", path.display(), why));
+        return;
+    }
+
+    let mut actual: Vec<String> = headless::framebuffer_ascii(&emu.gfx, emu.width(), emu.height()).lines().map(|line| line.to_string()).collect();
+    if pbm {
+        //PBM images only record whether a pixel is lit
+        actual = actual.iter().map(|row| row.chars().map(|pixel| if pixel == '.' { '.' } else { '#' }).collect()).collect();
+    }
+    let expected = read_golden(&path);
+
+    let mut diff = String::new();
+    if expected.len() != actual.len() || expected.first().map(|row| row.len()) != actual.first().map(|row| row.len()) {
+        diff.push_str(&format!("expected a {}x{} image but the framebuffer is {}x{}\n",
+            expected.first().map_or(0, |row| row.len()), expected.len(), emu.width(), emu.height()));
+    }
+    for (row, (expected_row, actual_row)) in expected.iter().zip(&actual).enumerate() {
+        if expected_row != actual_row {
+            let marker: String = expected_row.chars().zip(actual_row.chars()).map(|(e, a)| if e == a { ' ' } else { '^' }).collect();
+            diff.push_str(&format!("row {:>2} expected: {}\n       actual:   {}\n                 {}\n", row, expected_row, actual_row, marker.trim_end()));
+        }
+    }
+    if !diff.is_empty() {
+        panic!("framebuffer does not match {}\n{}", path.display(), diff);
+    }
+}
+
+#[test]
+fn font() {
+    let (emu, run) = run_rom("font.8o", Quirks::default(), 60, &[]);
+    assert_eq!(run.stop, StopReason::Finished);
+    assert_golden(&emu, "font.txt");
+}
+
+#[test]
+fn font_pbm() {
+    let (emu, _) = run_rom("font.8o", Quirks::default(), 60, &[]);
+    assert_golden(&emu, "font.pbm");
+}
+
+//The small font as published for the COSMAC VIP interpreter, to check font.8o against data not taken from the emulator
+const REFERENCE_FONT: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], [0x20, 0x60, 0x20, 0x20, 0x70], [0xF0, 0x10, 0xF0, 0x80, 0xF0], [0xF0, 0x10, 0xF0, 0x10, 0xF0],
+    [0x90, 0x90, 0xF0, 0x10, 0x10], [0xF0, 0x80, 0xF0, 0x10, 0xF0], [0xF0, 0x80, 0xF0, 0x90, 0xF0], [0xF0, 0x10, 0x20, 0x40, 0x40],
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], [0xF0, 0x90, 0xF0, 0x10, 0xF0], [0xF0, 0x90, 0xF0, 0x90, 0x90], [0xE0, 0x90, 0xE0, 0x90, 0xE0],
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], [0xE0, 0x90, 0x90, 0x90, 0xE0], [0xF0, 0x80, 0xF0, 0x80, 0xF0], [0xF0, 0x80, 0xF0, 0x80, 0x80]
+];
+
+#[test]
+fn font_matches_the_reference_font() {
+    let (emu, _) = run_rom("font.8o", Quirks::default(), 60, &[]);
+    for (digit, rows) in REFERENCE_FONT.iter().enumerate() {
+        let (x, y) = (1 + digit % 8 * 8, 1 + digit / 8 * 8);
+        for (row, bits) in rows.iter().enumerate() {
+            let actual: Vec<bool> = (0..8).map(|column| emu.gfx[(y + row) * emu.width() + x + column] != 0).collect();
+            let expected: Vec<bool> = (0..8).map(|column| bits & (0x80 >> column) != 0).collect();
+            assert_eq!(actual, expected, "row {} of digit {:X}", row, digit);
+        }
+    }
+}
+
+#[test]
+fn bcd() {
+    let (emu, _) = run_rom("bcd.8o", Quirks::default(), 10, &[]);
+    assert_eq!((emu.register(0), emu.register(1), emu.register(2)), (1, 3, 7));
+    assert_golden(&emu, "bcd.txt");
+}
+
+#[test]
+fn hires_big_font_and_scrolling() {
+    let (emu, _) = run_rom("hires.8o", Quirks::default(), 10, &[]);
+    assert_eq!((emu.width(), emu.height()), (128, 64));
+    assert_golden(&emu, "hires.txt");
+}
+
+#[test]
+fn xochip_planes() {
+    let (emu, _) = run_rom("planes.8o", Quirks::profile("xochip").unwrap(), 10, &[]);
+    assert_golden(&emu, "planes.txt");
+}
+
+#[test]
+fn sprites_clip_at_the_edges() {
+    let (emu, _) = run_rom("edges.8o", Quirks::profile("schip").unwrap(), 10, &[]);
+    assert_golden(&emu, "edges_clipped.txt");
+}
+
+#[test]
+fn sprites_wrap_around_the_edges() {
+    let (emu, _) = run_rom("edges.8o", Quirks::profile("xochip").unwrap(), 10, &[]);
+    assert_golden(&emu, "edges_wrapped.pbm");
+}
+
+#[test]
+fn scripted_keys() {
+    let keys = [ScriptedKey { frame: 5, key: 0xB, pressed: true }];
+    let (emu, run) = run_rom("keys.8o", Quirks::default(), 60, &keys);
+    assert_eq!(run.stop, StopReason::Exited);
+    //The key goes down before frame 5 and the ROM exits within that frame
+    assert_eq!(run.frames, 5);
+    assert_golden(&emu, "keys.txt");
+}
+
+//VX, VY and VF after each case in flags.8o
+fn flags_expected(quirks: Quirks) -> Vec<[u8; 3]> {
+    let logic_vf = if quirks.vf_reset { 0 } else { 0x55 };
+    let (shift_right, shift_left) = if quirks.shifting { ([0x02, 0x82, 1], [0x82, 0x82, 0]) } else { ([0x41, 0x82, 0], [0x04, 0x82, 1]) };
+    vec![
+        [0xFF, 0xF0, logic_vf],
+        [0x0C, 0x0F, logic_vf],
+        [0x33, 0x0F, logic_vf],
+        [0x30, 0x20, 0],
+        [0x10, 0x20, 1],
+        [0x20, 0x10, 1],
+        [0xE0, 0x30, 0],
+        [0x00, 0x10, 1],
+        [0x20, 0x30, 1],
+        [0xE0, 0x10, 0],
+        shift_right,
+        shift_left,
+        [0x00, 0x20, 1],
+        [0x00, 0x30, 0]
+    ]
+}
+
+fn check_flags(quirks: Quirks) {
+    let results = 0x200 + assemble("flags.8o").len();
+    let (emu, run) = run_rom("flags.8o", quirks, 60, &[]);
+    assert_eq!(run.stop, StopReason::Finished);
+    let expected = flags_expected(quirks);
+    let actual: Vec<[u8; 3]> = emu.memory()[results..results + expected.len()*3].chunks(3).map(|case| [case[0], case[1], case[2]]).collect();
+    for (case, (expected, actual)) in expected.iter().zip(&actual).enumerate() {
+        assert_eq!(expected, actual, "case {} of flags.8o with {:?}", case + 1, quirks);
+    }
+}
+
+#[test]
+fn flags() {
+    check_flags(Quirks::default());
+}
+
+#[test]
+fn flags_with_vf_reset_and_without_shifting() {
+    check_flags(Quirks::profile("vip").unwrap());
+}
+
+#[test]
+fn quirks_of_every_profile() {
+    for name in PROFILES.iter() {
+        let quirks = Quirks::profile(name).unwrap();
+        let (emu, _) = run_rom("quirks.8o", quirks, 10, &[]);
+        assert_eq!(emu.register(0xA), if quirks.vf_reset { 0 } else { 5 }, "vf_reset in {}", name);
+        assert_eq!(emu.register(0xB), if quirks.memory { 0xAA } else { 0x11 }, "memory in {}", name);
+        assert_eq!(emu.register(0xC), if quirks.shifting { 0x02 } else { 0x41 }, "shifting in {}", name);
+        assert_eq!(emu.register(0xD), if quirks.jumping { 2 } else { 0 }, "jumping in {}", name);
+    }
+}
+
+#[test]
+fn quirks_can_be_flipped_one_at_a_time() {
+    let registers = |emu: &Chip8| [emu.register(0xA), emu.register(0xB), emu.register(0xC), emu.register(0xD)];
+    let (emu, _) = run_rom("quirks.8o", Quirks::default(), 10, &[]);
+    let classic = registers(&emu);
+    assert_eq!(classic, [5, 0x11, 0x02, 0]);
+
+    let flipped = [("vf_reset", true, 0), ("memory", true, 0xAA), ("shifting", false, 0x41), ("jumping", true, 2)];
+    for (register, (flag, on, value)) in flipped.iter().enumerate() {
+        let mut quirks = Quirks::default();
+        assert!(quirks.set(flag, *on));
+        let (emu, _) = run_rom("quirks.8o", quirks, 10, &[]);
+        let mut expected = classic;
+        expected[register] = *value;
+        assert_eq!(registers(&emu), expected, "{} flipped", flag);
+    }
+}
+
+#[test]
+fn display_wait_draws_one_sprite_per_frame() {
+    let (_, run) = run_rom("draws.8o", Quirks::profile("vip").unwrap(), 10, &[]);
+    assert_eq!((run.stop, run.frames), (StopReason::Exited, 3));
+    let (_, run) = run_rom("draws.8o", Quirks::profile("schip").unwrap(), 10, &[]);
+    assert_eq!((run.stop, run.frames), (StopReason::Exited, 0));
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_chip8_logo() {
+    let emu = run_suite_rom("1-chip8-logo.ch8", Quirks::default(), None, 60, &[]);
+    assert_golden(&emu, "suite/1-chip8-logo.txt");
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_ibm_logo() {
+    let emu = run_suite_rom("2-ibm-logo.ch8", Quirks::default(), None, 60, &[]);
+    assert_golden(&emu, "suite/2-ibm-logo.txt");
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_opcodes() {
+    let emu = run_suite_rom("3-corax+.ch8", Quirks::default(), None, 60, &[]);
+    assert_golden(&emu, "suite/3-corax+.txt");
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_flags() {
+    let emu = run_suite_rom("4-flags.ch8", Quirks::default(), None, 60, &[]);
+    assert_golden(&emu, "suite/4-flags.txt");
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_quirks() {
+    for (platform, profile) in [(1, "vip"), (2, "schip"), (3, "xochip")].iter() {
+        let emu = run_suite_rom("5-quirks.ch8", Quirks::profile(profile).unwrap(), Some(*platform), 600, &[]);
+        assert_golden(&emu, &format!("suite/5-quirks-{}.txt", profile));
+    }
+}
+
+#[test]
+#[ignore = "needs the community test suite ROMs and reference images, see the README"]
+fn suite_keypad() {
+    //The FX0A test passes when a key is pressed and released
+    let keys = [ScriptedKey { frame: 30, key: 0xA, pressed: true }, ScriptedKey { frame: 40, key: 0xA, pressed: false }];
+    let emu = run_suite_rom("6-keypad.ch8", Quirks::default(), Some(3), 120, &keys);
+    assert_golden(&emu, "suite/6-keypad.txt");
+}
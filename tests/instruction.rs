@@ -0,0 +1,30 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//Opcodes decode on their own with decode, decode_at also reads the address word of F000
+
+use chip8::instruction::{decode, decode_at, Instruction};
+
+#[test]
+fn decodes_single_opcodes() {
+    assert_eq!(decode(0x00E0), Instruction::Clear);
+    assert_eq!(decode(0x1234), Instruction::Jump { address: 0x234 });
+    assert_eq!(decode(0x8AB4), Instruction::Add { x: 0xA, y: 0xB });
+    assert_eq!(decode(0xF000), Instruction::Unknown { opcode: 0xF000 });
+}
+
+#[test]
+fn decodes_the_long_form_with_its_address() {
+    assert_eq!(decode_at(&[0xF0, 0x00, 0x12, 0x34]), Some((Instruction::SetILong { address: 0x1234 }, 4)));
+    assert_eq!(decode_at(&[0xF0, 0x00, 0x12]), None);
+    assert_eq!(decode_at(&[0x00, 0xE0, 0xF0]), Some((Instruction::Clear, 2)));
+}
@@ -0,0 +1,127 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//Tests of the Octo assembler's output and of where it reports errors
+
+use chip8::octo::{assemble, AssemblyError};
+use chip8::{Chip8, Quirks};
+
+fn error(source: &str) -> AssemblyError {
+    match assemble(source) {
+        Ok(rom) => panic!("expected an error but assembled {:02X?}", rom),
+        Err(error) => error
+    }
+}
+
+//Runs an assembled program for a number of instructions
+fn run(source: &str, cycles: usize) -> Chip8 {
+    let rom = assemble(source).unwrap_or_else(|error| panic!("{}", error));
+    let mut emu = chip8::init(rom, Quirks::default());
+    for _ in 0..cycles {
+        emu.cycle(false).unwrap();
+    }
+    emu
+}
+
+#[test]
+fn errors_report_line_and_column() {
+    let undefined = error("v0 := 1\nv1 := 2\n  jump nowhere");
+    assert_eq!((undefined.line, undefined.column), (3, 8));
+    assert_eq!(undefined.message, "Undefined name 'nowhere'");
+    assert_eq!(undefined.to_string(), "3:8: Undefined name 'nowhere'");
+
+    let out_of_range = error("clear\n\tv0 := 300");
+    assert_eq!((out_of_range.line, out_of_range.column), (2, 8));
+
+    let unknown = error("clear # a comment\n    frobnicate");
+    assert_eq!((unknown.line, unknown.column), (2, 5));
+
+    let unclosed = error("clear\n  loop\n    clear");
+    assert_eq!((unclosed.line, unclosed.column), (2, 3));
+    assert_eq!(unclosed.message, "'loop' without 'again'");
+
+    let unfinished = error("if v0 == 1 begin\n  clear");
+    assert_eq!((unfinished.line, unfinished.column), (1, 1));
+
+    let unreachable = error(":org 0x1000\n  loop clear again");
+    assert_eq!((unreachable.line, unreachable.column), (2, 3));
+    assert_eq!(unreachable.message, "Loop start 0x1000 does not fit in 12 bits");
+}
+
+#[test]
+fn errors_in_macros_point_into_the_macro_body() {
+    let error = error(":macro bad { v0 := 999 }\nclear\n  bad");
+    assert_eq!((error.line, error.column), (1, 20));
+}
+
+#[test]
+fn if_then_skips_the_next_statement() {
+    assert_eq!(assemble("if v0 == 5 then v1 := 1").unwrap(), [0x40, 0x05, 0x61, 0x01]);
+    assert_eq!(assemble("if v0 != v2 then v1 := 1").unwrap(), [0x50, 0x20, 0x61, 0x01]);
+    assert_eq!(assemble("if v3 key then clear").unwrap(), [0xE3, 0xA1, 0x00, 0xE0]);
+}
+
+#[test]
+fn if_else_patches_both_jumps() {
+    let rom = assemble("if v0 == 5 begin v1 := 1 else v1 := 2 end v2 := 3").unwrap();
+    assert_eq!(rom, [
+        0x30, 0x05, //Skip the jump to else when V0 == 5
+        0x12, 0x08, //Jump to else
+        0x61, 0x01,
+        0x12, 0x0A, //Jump over else to end
+        0x61, 0x02, //else
+        0x62, 0x03  //end
+    ]);
+}
+
+#[test]
+fn if_else_takes_the_right_branch() {
+    let program = |v0: u8| format!("v0 := {}\nif v0 == 5 begin v1 := 1 else v1 := 2 end v2 := 3\nloop again", v0);
+    let taken = run(&program(5), 5);
+    assert_eq!((taken.register(1), taken.register(2)), (1, 3));
+    let not_taken = run(&program(4), 5);
+    assert_eq!((not_taken.register(1), not_taken.register(2)), (2, 3));
+}
+
+#[test]
+fn comparisons_set_up_vf() {
+    //Each true comparison adds one to V2
+    let source = "
+        v0 := 3
+        v1 := 7
+        if v0 < v1 then v2 += 1
+        if v0 < 3 then v2 += 0x10
+        if v0 <= 3 then v2 += 1
+        if v1 > 6 then v2 += 1
+        if v1 >= 8 then v2 += 0x10
+        if v1 > v0 then v2 += 1
+        loop again";
+    let emu = run(source, 40);
+    assert_eq!(emu.register(2), 4);
+}
+
+#[test]
+fn macros_expand_with_arguments() {
+    let rom = assemble(":macro twice X { X X }\n: main\n  twice clear\n").unwrap();
+    assert_eq!(rom, [0x12, 0x02, 0x00, 0xE0, 0x00, 0xE0]);
+
+    let rom = assemble(":macro set REG VALUE { REG := VALUE }\nset v4 0x22\nset v5 0x33").unwrap();
+    assert_eq!(rom, [0x64, 0x22, 0x65, 0x33]);
+}
+
+#[test]
+fn constants_aliases_and_forward_labels() {
+    assert_eq!(assemble(":const C 7\n:alias x v3\nx := C").unwrap(), [0x63, 0x07]);
+    //The label is used before it is defined and filled in at the end
+    assert_eq!(assemble("i := data\nclear\n: data 0xAA").unwrap(), [0xA2, 0x04, 0x00, 0xE0, 0xAA]);
+    assert_eq!(assemble("i := long data\n: data 0xAA").unwrap(), [0xF0, 0x00, 0x02, 0x04, 0xAA]);
+}
@@ -0,0 +1,132 @@
+/*
+CHIP-8 Emulator
+Copyright (C) 2021 Lily Jaroszek
+
+This program is free software: you can redistribute it and/or modify it under the terms of the GNU Affero General 
+Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any
+later version. This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even
+the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU Affero General Public License
+for more details. You should have received a copy of the GNU Affero General Public License along with this program. If
+not, see <https://www.gnu.org/licenses/>.
+*/
+
+//Save states have to restore the whole machine and reject states from other ROMs or format versions
+
+use chip8::savestate::{self, SaveStateError, VERSION};
+use chip8::{octo, Chip8, Quirks};
+
+//Sets registers, I, a timer and the display, then waits in a subroutine so the stack is in use
+const PROGRAM: &str = "
+: main
+    v0 := 5
+    v1 := 10
+    v3 := 0xAB
+    i := hex v0
+    sprite v1 v1 5
+    delay := v0
+    sub
+    loop again
+: sub
+    ve := 0x42
+    loop again
+";
+
+fn machine() -> (Chip8, u64) {
+    let rom = octo::assemble(PROGRAM).unwrap();
+    let rom_hash = savestate::hash(&rom);
+    (chip8::init(rom, Quirks::default()), rom_hash)
+}
+
+fn running_machine() -> (Chip8, u64) {
+    let (mut emu, rom_hash) = machine();
+    for _ in 0..12 {
+        emu.cycle(false).unwrap();
+    }
+    (emu, rom_hash)
+}
+
+#[test]
+fn round_trip_restores_the_machine() {
+    let (emu, rom_hash) = running_machine();
+    let state = savestate::encode(&emu, rom_hash);
+
+    let (mut restored, _) = machine();
+    savestate::decode(&mut restored, &state, rom_hash).unwrap();
+
+    assert_eq!(restored.pc(), emu.pc());
+    assert_eq!(restored.i(), emu.i());
+    assert_eq!(restored.sp(), 1);
+    assert_eq!(restored.sp(), emu.sp());
+    assert_eq!(restored.delay_timer(), emu.delay_timer());
+    for reg in 0..16 {
+        assert_eq!(restored.register(reg), emu.register(reg), "V{:X}", reg);
+    }
+    assert_eq!(restored.register(0xE), 0x42);
+    assert_eq!(restored.gfx, emu.gfx);
+    assert!(restored.gfx.iter().any(|&pixel| pixel != 0));
+    assert_eq!(restored.memory(), emu.memory());
+    //Anything the checks above missed still shows up in the encoded state
+    assert_eq!(savestate::encode(&restored, rom_hash), state);
+}
+
+#[test]
+fn restored_machine_continues_like_the_original() {
+    let (mut emu, rom_hash) = running_machine();
+    let state = savestate::encode(&emu, rom_hash);
+    let (mut restored, _) = machine();
+    savestate::decode(&mut restored, &state, rom_hash).unwrap();
+    for _ in 0..20 {
+        emu.cycle(false).unwrap();
+        restored.cycle(false).unwrap();
+    }
+    assert_eq!(savestate::encode(&restored, rom_hash), savestate::encode(&emu, rom_hash));
+}
+
+#[test]
+fn rejects_a_different_rom() {
+    let (emu, rom_hash) = running_machine();
+    let state = savestate::encode(&emu, rom_hash);
+    let (mut other, _) = machine();
+    let result = savestate::decode(&mut other, &state, rom_hash ^ 1);
+    assert!(matches!(result, Err(SaveStateError::DifferentRom)), "{:?}", result);
+    //A rejected state leaves the machine as it was
+    assert_eq!(other.pc(), 0x200);
+}
+
+#[test]
+fn rejects_another_format_version() {
+    let (emu, rom_hash) = running_machine();
+    let mut state = savestate::encode(&emu, rom_hash);
+    state[4..6].copy_from_slice(&(VERSION + 1).to_be_bytes());
+    let (mut other, _) = machine();
+    let result = savestate::decode(&mut other, &state, rom_hash);
+    assert!(matches!(result, Err(SaveStateError::IncompatibleVersion { version }) if version == VERSION + 1), "{:?}", result);
+    assert_eq!(other.pc(), 0x200);
+}
+
+#[test]
+fn rejects_truncated_and_padded_states() {
+    let (emu, rom_hash) = running_machine();
+    let state = savestate::encode(&emu, rom_hash);
+    let (mut other, _) = machine();
+
+    let result = savestate::decode(&mut other, &state[..state.len() - 1], rom_hash);
+    assert!(matches!(result, Err(SaveStateError::Corrupt)), "{:?}", result);
+    let result = savestate::decode(&mut other, &state[..state.len() / 2], rom_hash);
+    assert!(matches!(result, Err(SaveStateError::Corrupt)), "{:?}", result);
+
+    let mut padded = state.clone();
+    padded.push(0);
+    let result = savestate::decode(&mut other, &padded, rom_hash);
+    assert!(matches!(result, Err(SaveStateError::Corrupt)), "{:?}", result);
+    assert_eq!(other.pc(), 0x200);
+}
+
+#[test]
+fn rejects_other_files() {
+    let (mut emu, rom_hash) = machine();
+    let result = savestate::decode(&mut emu, b"not a save state", rom_hash);
+    assert!(matches!(result, Err(SaveStateError::NotASaveState)), "{:?}", result);
+    let result = savestate::decode(&mut emu, b"C8ST", rom_hash);
+    assert!(matches!(result, Err(SaveStateError::NotASaveState)), "{:?}", result);
+}